@@ -0,0 +1,120 @@
+//! Just enough AML parsing to find the sleep type values in the DSDT.
+
+const NAME_OP: u8 = 0x08;
+const PACKAGE_OP: u8 = 0x12;
+const BYTE_PREFIX: u8 = 0x0a;
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+
+/// Values written to the `SLP_TYP` fields of the PM1 control registers to
+/// enter a sleep state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepType {
+    pub pm1a: u8,
+    pub pm1b: u8,
+}
+
+/// Find the package `name` (e.g. `_S5_`) in the AML of the DSDT and
+/// return the sleep type values it contains.
+///
+/// Only the common encoding `Name(_S5_, Package() { a, b, ... })` with
+/// constant values is understood.
+pub fn sleep_type(dsdt: &[u8], name: &[u8; 4]) -> Option<SleepType> {
+    let position = dsdt
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| window == name)
+        .map(|(position, _)| position)
+        .find(|&position| is_defined_at(dsdt, position))?;
+
+    let mut bytes = dsdt[position + 4..].iter().copied();
+    if bytes.next()? != PACKAGE_OP {
+        return None;
+    }
+    // the upper two bits of the first byte encode the number of
+    // additional bytes of the package length
+    let length_bytes = bytes.next()? >> 6;
+    for _ in 0..length_bytes {
+        bytes.next()?;
+    }
+    let _elements = bytes.next()?;
+
+    let mut value = || match bytes.next()? {
+        BYTE_PREFIX => bytes.next(),
+        ZERO_OP => Some(0),
+        ONE_OP => Some(1),
+        _ => None,
+    };
+
+    Some(SleepType {
+        pm1a: value()?,
+        pm1b: value()?,
+    })
+}
+
+/// Returns whether the name at `position` is defined by a `Name` operator,
+/// possibly in the root scope.
+fn is_defined_at(dsdt: &[u8], position: usize) -> bool {
+    match position {
+        0 => false,
+        1 => dsdt[0] == NAME_OP,
+        _ => dsdt[position - 1] == NAME_OP || dsdt[position - 2..position] == [NAME_OP, b'\\'],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_sleep_type() {
+        // Name (_S5_, Package (0x04) { Zero, Zero, Zero, Zero })
+        let aml = [
+            0x10, NAME_OP, b'_', b'S', b'5', b'_', PACKAGE_OP, 0x06, 0x04, 0, 0, 0, 0,
+        ];
+        assert_eq!(
+            sleep_type(&aml, b"_S5_"),
+            Some(SleepType { pm1a: 0, pm1b: 0 })
+        );
+
+        // Name (\_S5_, Package (0x02) { 0x05, One })
+        let aml = [
+            NAME_OP,
+            b'\\',
+            b'_',
+            b'S',
+            b'5',
+            b'_',
+            PACKAGE_OP,
+            0x05,
+            0x02,
+            BYTE_PREFIX,
+            5,
+            ONE_OP,
+        ];
+        assert_eq!(
+            sleep_type(&aml, b"_S5_"),
+            Some(SleepType { pm1a: 5, pm1b: 1 })
+        );
+    }
+
+    #[test_case]
+    fn test_sleep_type_missing() {
+        let aml = [
+            NAME_OP, b'_', b'S', b'4', b'_', PACKAGE_OP, 0x04, 0x02, 0, 0,
+        ];
+        assert_eq!(sleep_type(&aml, b"_S5_"), None);
+
+        // a method call, not a definition
+        let aml = [0x14, b'_', b'S', b'5', b'_', PACKAGE_OP, 0x04, 0x02, 0, 0];
+        assert_eq!(sleep_type(&aml, b"_S5_"), None);
+    }
+
+    #[test_case]
+    fn test_firmware_defines_s5() {
+        let dsdt = crate::acpi::tables()
+            .and_then(|tables| tables.dsdt)
+            .expect("no DSDT");
+        assert!(sleep_type(dsdt.data(), b"_S5_").is_some());
+    }
+}
@@ -0,0 +1,147 @@
+use core::fmt;
+
+use x86_64::PhysAddr;
+
+use super::{read_u16, read_u32, read_u64, read_u8, GenericAddress};
+
+/// The reset register is supported.
+const FLAG_RESET_REGISTER: u32 = 1 << 10;
+
+/// The system has an 8042 keyboard controller.
+pub const BOOT_ARCH_8042: u16 = 1 << 1;
+
+/// Fixed ACPI Description Table.
+///
+/// Port fields are 0 if the block is not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fadt {
+    /// Address of the Differentiated System Description Table.
+    pub dsdt: PhysAddr,
+    /// ISA IRQ of the System Control Interrupt.
+    pub sci_interrupt: u16,
+    /// Port to which `acpi_enable` is written to switch to ACPI mode.
+    pub smi_command_port: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub pm1a_control_block: u32,
+    pub pm1b_control_block: u32,
+    pub pm_timer_block: u32,
+    /// Index of the century register in the CMOS RAM, 0 if there is none.
+    pub century_register: u8,
+    /// IA-PC boot architecture flags, see [`BOOT_ARCH_8042`].
+    pub boot_architecture: u16,
+    pub flags: u32,
+    /// Register to which `reset_value` is written to reset the system.
+    pub reset_register: Option<GenericAddress>,
+    pub reset_value: u8,
+}
+
+impl Fadt {
+    pub const SIGNATURE: &'static str = "FACP";
+
+    /// Parse the complete table, including the header.
+    ///
+    /// Fields which were only added in later revisions are optional.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let dsdt = read_u64(data, 140)
+            .filter(|&address| address != 0)
+            .unwrap_or(u64::from(read_u32(data, 40)?));
+        let flags = read_u32(data, 112).unwrap_or(0);
+        let reset_register = GenericAddress::parse(data, 116)
+            .filter(|_| flags & FLAG_RESET_REGISTER != 0)
+            .filter(|register| register.address != 0);
+
+        Some(Self {
+            dsdt: PhysAddr::try_new(dsdt).ok()?,
+            sci_interrupt: read_u16(data, 46)?,
+            smi_command_port: read_u32(data, 48)?,
+            acpi_enable: read_u8(data, 52)?,
+            acpi_disable: read_u8(data, 53)?,
+            pm1a_control_block: read_u32(data, 64)?,
+            pm1b_control_block: read_u32(data, 68)?,
+            pm_timer_block: read_u32(data, 76)?,
+            century_register: read_u8(data, 108).unwrap_or(0),
+            boot_architecture: read_u16(data, 109).unwrap_or(0),
+            flags,
+            reset_register,
+            reset_value: read_u8(data, 128).unwrap_or(0),
+        })
+    }
+}
+
+impl fmt::Display for Fadt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FADT: DSDT at {:#x}, SCI IRQ {}, PM1a {:#x}, PM timer {:#x}",
+            self.dsdt.as_u64(),
+            self.sci_interrupt,
+            self.pm1a_control_block,
+            self.pm_timer_block
+        )?;
+        if let Some(reset) = self.reset_register {
+            write!(
+                f,
+                "\n  reset: {:#x} ({:?}) <- {:#x}",
+                reset.address, reset.space, self.reset_value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acpi::AddressSpaceId;
+
+    fn fadt(len: usize) -> [u8; 244] {
+        let mut data = [0; 244];
+        data[..4].copy_from_slice(b"FACP");
+        data[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+        data[40..44].copy_from_slice(&0x1234u32.to_le_bytes());
+        data[46] = 9;
+        data[64..68].copy_from_slice(&0x604u32.to_le_bytes());
+        data
+    }
+
+    #[test_case]
+    fn test_parse_revision_1() {
+        let data = fadt(116);
+        let fadt = Fadt::parse(&data[..116]).expect("parsing failed");
+
+        assert_eq!(fadt.dsdt, PhysAddr::new(0x1234));
+        assert_eq!(fadt.sci_interrupt, 9);
+        assert_eq!(fadt.pm1a_control_block, 0x604);
+        assert_eq!(fadt.reset_register, None);
+    }
+
+    #[test_case]
+    fn test_parse_reset_register() {
+        let mut data = fadt(244);
+        data[112..116].copy_from_slice(&FLAG_RESET_REGISTER.to_le_bytes());
+        data[116] = 1;
+        data[120..128].copy_from_slice(&0xcf9u64.to_le_bytes());
+        data[128] = 0x6;
+        data[140..148].copy_from_slice(&0x5678u64.to_le_bytes());
+        let fadt = Fadt::parse(&data).expect("parsing failed");
+
+        assert_eq!(fadt.dsdt, PhysAddr::new(0x5678));
+        let reset = fadt.reset_register.expect("no reset register");
+        assert_eq!(reset.space, AddressSpaceId::SystemIo);
+        assert_eq!(reset.address, 0xcf9);
+        assert_eq!(fadt.reset_value, 0x6);
+    }
+
+    #[test_case]
+    fn test_parse_invalid_dsdt_address() {
+        let mut data = fadt(244);
+        data[140..148].copy_from_slice(&0xffff_0000_0000_5678u64.to_le_bytes());
+        assert_eq!(Fadt::parse(&data), None);
+    }
+
+    #[test_case]
+    fn test_parse_truncated() {
+        assert_eq!(Fadt::parse(&fadt(60)[..60]), None);
+    }
+}
@@ -0,0 +1,81 @@
+use core::fmt;
+
+use super::{read_u16, read_u32, read_u8, GenericAddress};
+
+/// High Precision Event Timer Description Table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hpet {
+    pub hardware_revision: u8,
+    pub comparators: u8,
+    /// Whether the main counter is 64 bits wide.
+    pub counter_64_bit: bool,
+    /// Whether the HPET can replace the PIT and RTC interrupts.
+    pub legacy_replacement: bool,
+    pub pci_vendor_id: u16,
+    /// Location of the registers, always in system memory.
+    pub base_address: GenericAddress,
+    pub number: u8,
+    /// Minimum period in periodic mode, in main counter ticks.
+    pub minimum_tick: u16,
+}
+
+impl Hpet {
+    pub const SIGNATURE: &'static str = "HPET";
+
+    /// Parse the complete table, including the header.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let id = read_u32(data, 36)?;
+
+        Some(Self {
+            hardware_revision: id as u8,
+            comparators: ((id >> 8) & 0x1f) as u8 + 1,
+            counter_64_bit: id & (1 << 13) != 0,
+            legacy_replacement: id & (1 << 15) != 0,
+            pci_vendor_id: (id >> 16) as u16,
+            base_address: GenericAddress::parse(data, 40)?,
+            number: read_u8(data, 52)?,
+            minimum_tick: read_u16(data, 53)?,
+        })
+    }
+}
+
+impl fmt::Display for Hpet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "HPET {}: {:#x}, {} comparators, {} bit counter{}",
+            self.number,
+            self.base_address.address,
+            self.comparators,
+            if self.counter_64_bit { 64 } else { 32 },
+            if self.legacy_replacement {
+                ", legacy replacement"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_parse() {
+        let mut data = [0; 56];
+        data[..4].copy_from_slice(b"HPET");
+        data[36..40].copy_from_slice(&0x8086_a201u32.to_le_bytes());
+        data[44..52].copy_from_slice(&0xfed0_0000u64.to_le_bytes());
+        data[53..55].copy_from_slice(&0x80u16.to_le_bytes());
+        let hpet = Hpet::parse(&data).expect("parsing failed");
+
+        assert_eq!(hpet.hardware_revision, 1);
+        assert_eq!(hpet.comparators, 3);
+        assert!(hpet.counter_64_bit);
+        assert!(hpet.legacy_replacement);
+        assert_eq!(hpet.pci_vendor_id, 0x8086);
+        assert_eq!(hpet.base_address.address, 0xfed0_0000);
+        assert_eq!(hpet.minimum_tick, 0x80);
+    }
+}
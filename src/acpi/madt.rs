@@ -0,0 +1,301 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use x86_64::PhysAddr;
+
+use super::{read_u16, read_u32, read_u64, read_u8};
+
+/// Offset of the first interrupt controller structure.
+const ENTRIES_OFFSET: usize = 44;
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_INTERRUPT_OVERRIDE: u8 = 2;
+const ENTRY_LOCAL_APIC_NMI: u8 = 4;
+const ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+const ENTRY_LOCAL_X2APIC: u8 = 9;
+
+const PROCESSOR_ENABLED: u32 = 1 << 0;
+const PROCESSOR_ONLINE_CAPABLE: u32 = 1 << 1;
+const PCAT_COMPAT: u32 = 1 << 0;
+
+/// A processor with its local APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Processor {
+    pub processor_id: u32,
+    pub apic_id: u32,
+    /// Whether the processor is usable right away.
+    pub enabled: bool,
+    /// Whether a disabled processor can be brought online later.
+    pub online_capable: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApic {
+    pub id: u8,
+    pub address: PhysAddr,
+    /// First global system interrupt handled by this I/O APIC.
+    pub gsi_base: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    /// As specified by the bus (active high for ISA).
+    Conforming,
+    ActiveHigh,
+    ActiveLow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// As specified by the bus (edge for ISA).
+    Conforming,
+    Edge,
+    Level,
+}
+
+fn polarity(flags: u16) -> Polarity {
+    match flags & 0b11 {
+        0b01 => Polarity::ActiveHigh,
+        0b11 => Polarity::ActiveLow,
+        _ => Polarity::Conforming,
+    }
+}
+
+fn trigger_mode(flags: u16) -> TriggerMode {
+    match (flags >> 2) & 0b11 {
+        0b01 => TriggerMode::Edge,
+        0b11 => TriggerMode::Level,
+        _ => TriggerMode::Conforming,
+    }
+}
+
+/// An ISA IRQ connected to a different global system interrupt than its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+}
+
+/// A local APIC input connected to the NMI line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalApicNmi {
+    /// `None` if it applies to all processors.
+    pub processor_id: Option<u8>,
+    /// LINT0 or LINT1.
+    pub lint: u8,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+}
+
+/// Multiple APIC Description Table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Madt {
+    pub local_apic_address: PhysAddr,
+    /// Whether the system also has dual 8259 PICs.
+    pub has_legacy_pics: bool,
+    pub processors: Vec<Processor>,
+    pub io_apics: Vec<IoApic>,
+    pub overrides: Vec<InterruptOverride>,
+    pub nmis: Vec<LocalApicNmi>,
+}
+
+impl Madt {
+    pub const SIGNATURE: &'static str = "APIC";
+
+    /// Parse the complete table, including the header.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut madt = Self {
+            local_apic_address: PhysAddr::try_new(u64::from(read_u32(data, 36)?)).ok()?,
+            has_legacy_pics: read_u32(data, 40)? & PCAT_COMPAT != 0,
+            processors: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+            nmis: Vec::new(),
+        };
+
+        let mut offset = ENTRIES_OFFSET;
+        while offset < data.len() {
+            let kind = read_u8(data, offset)?;
+            let length = usize::from(read_u8(data, offset + 1)?);
+            if length < 2 {
+                return None;
+            }
+            let entry = data.get(offset..offset + length)?;
+
+            match kind {
+                ENTRY_LOCAL_APIC => {
+                    let flags = read_u32(entry, 4)?;
+                    madt.processors.push(Processor {
+                        processor_id: u32::from(read_u8(entry, 2)?),
+                        apic_id: u32::from(read_u8(entry, 3)?),
+                        enabled: flags & PROCESSOR_ENABLED != 0,
+                        online_capable: flags & PROCESSOR_ONLINE_CAPABLE != 0,
+                    });
+                }
+                ENTRY_LOCAL_X2APIC => {
+                    let flags = read_u32(entry, 8)?;
+                    madt.processors.push(Processor {
+                        processor_id: read_u32(entry, 12)?,
+                        apic_id: read_u32(entry, 4)?,
+                        enabled: flags & PROCESSOR_ENABLED != 0,
+                        online_capable: flags & PROCESSOR_ONLINE_CAPABLE != 0,
+                    });
+                }
+                ENTRY_IO_APIC => madt.io_apics.push(IoApic {
+                    id: read_u8(entry, 2)?,
+                    address: PhysAddr::try_new(u64::from(read_u32(entry, 4)?)).ok()?,
+                    gsi_base: read_u32(entry, 8)?,
+                }),
+                ENTRY_INTERRUPT_OVERRIDE => {
+                    let flags = read_u16(entry, 8)?;
+                    madt.overrides.push(InterruptOverride {
+                        irq: read_u8(entry, 3)?,
+                        gsi: read_u32(entry, 4)?,
+                        polarity: polarity(flags),
+                        trigger_mode: trigger_mode(flags),
+                    });
+                }
+                ENTRY_LOCAL_APIC_NMI => {
+                    let flags = read_u16(entry, 3)?;
+                    madt.nmis.push(LocalApicNmi {
+                        processor_id: Some(read_u8(entry, 2)?).filter(|&id| id != 0xff),
+                        lint: read_u8(entry, 5)?,
+                        polarity: polarity(flags),
+                        trigger_mode: trigger_mode(flags),
+                    });
+                }
+                ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE => {
+                    madt.local_apic_address = PhysAddr::try_new(read_u64(entry, 4)?).ok()?;
+                }
+                _ => {}
+            }
+
+            offset += length;
+        }
+
+        Some(madt)
+    }
+
+    /// Returns the global system interrupt the ISA IRQ is connected to.
+    pub fn isa_irq_to_gsi(&self, irq: u8) -> u32 {
+        self.overrides
+            .iter()
+            .find(|o| o.irq == irq)
+            .map_or(u32::from(irq), |o| o.gsi)
+    }
+}
+
+impl fmt::Display for Madt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MADT: local APIC at {:#x}{}",
+            self.local_apic_address.as_u64(),
+            if self.has_legacy_pics {
+                ", 8259 PICs"
+            } else {
+                ""
+            }
+        )?;
+        for cpu in &self.processors {
+            write!(
+                f,
+                "\n  CPU {}: APIC ID {}{}",
+                cpu.processor_id,
+                cpu.apic_id,
+                if cpu.enabled { "" } else { " (disabled)" }
+            )?;
+        }
+        for io_apic in &self.io_apics {
+            write!(
+                f,
+                "\n  I/O APIC {}: {:#x}, GSI base {}",
+                io_apic.id,
+                io_apic.address.as_u64(),
+                io_apic.gsi_base
+            )?;
+        }
+        for o in &self.overrides {
+            write!(
+                f,
+                "\n  IRQ {} -> GSI {} ({:?}, {:?})",
+                o.irq, o.gsi, o.polarity, o.trigger_mode
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Also used to test the I/O APIC routing.
+    #[rustfmt::skip]
+    pub(crate) const MADT: &[u8] = &[
+        // header
+        b'A', b'P', b'I', b'C', 98, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // local APIC address, flags
+        0x00, 0x00, 0xe0, 0xfe, 1, 0, 0, 0,
+        // processor 0, APIC ID 0, enabled
+        0, 8, 0, 0, 1, 0, 0, 0,
+        // processor 1, APIC ID 1, disabled
+        0, 8, 1, 1, 0, 0, 0, 0,
+        // I/O APIC 0 at 0xfec00000, GSI base 0
+        1, 12, 0, 0, 0x00, 0x00, 0xc0, 0xfe, 0, 0, 0, 0,
+        // IRQ 0 -> GSI 2, conforming
+        2, 10, 0, 0, 2, 0, 0, 0, 0, 0,
+        // IRQ 9 -> GSI 9, active high, level triggered
+        2, 10, 0, 9, 9, 0, 0, 0, 0b1101, 0,
+        // NMI on LINT1 of all processors
+        4, 6, 0xff, 0, 0, 1,
+    ];
+
+    #[test_case]
+    fn test_parse() {
+        let madt = Madt::parse(MADT).expect("parsing failed");
+
+        assert_eq!(madt.local_apic_address, PhysAddr::new(0xfee0_0000));
+        assert!(madt.has_legacy_pics);
+        assert_eq!(madt.processors.len(), 2);
+        assert!(madt.processors[0].enabled);
+        assert!(!madt.processors[1].enabled);
+        assert_eq!(
+            madt.io_apics,
+            [IoApic {
+                id: 0,
+                address: PhysAddr::new(0xfec0_0000),
+                gsi_base: 0,
+            }]
+        );
+        assert_eq!(madt.overrides[1].polarity, Polarity::ActiveHigh);
+        assert_eq!(madt.overrides[1].trigger_mode, TriggerMode::Level);
+        assert_eq!(madt.nmis[0].processor_id, None);
+        assert_eq!(madt.nmis[0].lint, 1);
+    }
+
+    #[test_case]
+    fn test_isa_irq_to_gsi() {
+        let madt = Madt::parse(MADT).expect("parsing failed");
+        assert_eq!(madt.isa_irq_to_gsi(0), 2);
+        assert_eq!(madt.isa_irq_to_gsi(1), 1);
+    }
+
+    #[test_case]
+    fn test_truncated_entry() {
+        assert_eq!(Madt::parse(&MADT[..50]), None);
+    }
+
+    #[test_case]
+    fn test_invalid_address() {
+        let mut data = MADT.to_vec();
+        // local APIC address override with bits above 52 set
+        data.extend_from_slice(&[5, 12, 0, 0]);
+        data.extend_from_slice(&0xffff_0000_fee0_0000u64.to_le_bytes());
+        assert_eq!(Madt::parse(&data), None);
+    }
+}
@@ -0,0 +1,299 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::slice;
+use core::str;
+
+use spin::Once;
+use x86_64::PhysAddr;
+
+use crate::mem::phys_to_virt;
+
+pub mod dsdt;
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+mod rsdp;
+
+use fadt::Fadt;
+use hpet::Hpet;
+use madt::Madt;
+
+/// Size of the header shared by all system description tables.
+const HEADER_SIZE: usize = 36;
+
+/// Errors returned while discovering the ACPI tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    /// No valid RSDP was found in the BIOS areas.
+    NoRsdp,
+    /// The table at the given address has a wrong checksum.
+    InvalidChecksum(PhysAddr),
+    /// The table at the given address is shorter than its contents.
+    Truncated(PhysAddr),
+    /// The table at the given address doesn't have the expected signature.
+    UnexpectedSignature(PhysAddr),
+}
+
+/// A system description table with a valid checksum.
+#[derive(Debug, Clone, Copy)]
+pub struct Sdt {
+    pub address: PhysAddr,
+    data: &'static [u8],
+}
+
+impl Sdt {
+    /// Load and validate the table at `address`.
+    ///
+    /// # Safety
+    ///
+    /// The address must point to a system description table.
+    unsafe fn load(address: PhysAddr) -> Result<Self, AcpiError> {
+        let header = unsafe { physical_bytes(address, HEADER_SIZE) };
+        let length = read_u32(header, 4).unwrap() as usize;
+        if length < HEADER_SIZE {
+            return Err(AcpiError::Truncated(address));
+        }
+
+        let data = unsafe { physical_bytes(address, length) };
+        if checksum(data) != 0 {
+            return Err(AcpiError::InvalidChecksum(address));
+        }
+
+        Ok(Self { address, data })
+    }
+
+    pub fn signature(&self) -> &'static str {
+        ascii(&self.data[..4])
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.data[8]
+    }
+
+    pub fn oem_id(&self) -> &'static str {
+        ascii(&self.data[10..16])
+    }
+
+    /// Returns the complete table, including the header.
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+
+    fn body(&self) -> &'static [u8] {
+        &self.data[HEADER_SIZE..]
+    }
+}
+
+/// Address space of a [`GenericAddress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpaceId {
+    SystemMemory,
+    SystemIo,
+    PciConfiguration,
+    Other(u8),
+}
+
+/// The location of a register, as described by ACPI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericAddress {
+    pub space: AddressSpaceId,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    fn parse(data: &[u8], offset: usize) -> Option<Self> {
+        let space = match read_u8(data, offset)? {
+            0 => AddressSpaceId::SystemMemory,
+            1 => AddressSpaceId::SystemIo,
+            2 => AddressSpaceId::PciConfiguration,
+            id => AddressSpaceId::Other(id),
+        };
+
+        Some(Self {
+            space,
+            bit_width: read_u8(data, offset + 1)?,
+            bit_offset: read_u8(data, offset + 2)?,
+            access_size: read_u8(data, offset + 3)?,
+            address: read_u64(data, offset + 4)?,
+        })
+    }
+}
+
+/// Everything the kernel learned from the ACPI tables.
+#[derive(Debug)]
+pub struct AcpiTables {
+    pub revision: u8,
+    pub oem_id: &'static str,
+    /// All tables listed in the RSDT or XSDT which have a valid checksum.
+    pub tables: Vec<Sdt>,
+    /// The Differentiated System Description Table, referenced by the FADT.
+    pub dsdt: Option<Sdt>,
+    pub madt: Option<Madt>,
+    pub fadt: Option<Fadt>,
+    pub hpet: Option<Hpet>,
+}
+
+impl AcpiTables {
+    /// Returns the first table with the given signature.
+    pub fn find(&self, signature: &str) -> Option<Sdt> {
+        self.tables
+            .iter()
+            .find(|table| table.signature() == signature)
+            .copied()
+    }
+}
+
+impl fmt::Display for AcpiTables {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ACPI revision {}, OEM {}", self.revision, self.oem_id)?;
+        write!(f, "Tables:")?;
+        for table in &self.tables {
+            write!(f, " {}", table.signature())?;
+        }
+        if let Some(madt) = &self.madt {
+            write!(f, "\n{}", madt)?;
+        }
+        if let Some(fadt) = &self.fadt {
+            write!(f, "\n{}", fadt)?;
+        }
+        if let Some(hpet) = &self.hpet {
+            write!(f, "\n{}", hpet)?;
+        }
+        Ok(())
+    }
+}
+
+static TABLES: Once<AcpiTables> = Once::new();
+
+/// Find and parse the ACPI tables.
+///
+/// Must be called after memory management is initialized. Tables which
+/// are invalid are left out.
+pub fn init() -> Result<(), AcpiError> {
+    let rsdp = rsdp::find().ok_or(AcpiError::NoRsdp)?;
+
+    // The XSDT contains 64 bit pointers, the RSDT 32 bit ones.
+    let (root, signature, entry_size) = match rsdp.xsdt_address {
+        Some(address) => (address, "XSDT", 8),
+        None => (rsdp.rsdt_address, "RSDT", 4),
+    };
+    // SAFETY: The address comes from a valid RSDP.
+    let root = unsafe { Sdt::load(root) }?;
+    if root.signature() != signature {
+        return Err(AcpiError::UnexpectedSignature(root.address));
+    }
+
+    let tables: Vec<Sdt> = root
+        .body()
+        .chunks_exact(entry_size)
+        .map(|entry| match entry_size {
+            8 => read_u64(entry, 0).unwrap(),
+            _ => u64::from(read_u32(entry, 0).unwrap()),
+        })
+        // entries with invalid addresses are skipped like invalid tables
+        .filter_map(|address| PhysAddr::try_new(address).ok())
+        // SAFETY: The addresses come from a valid root table.
+        .filter_map(|address| unsafe { Sdt::load(address) }.ok())
+        .collect();
+
+    let find = |signature| tables.iter().find(|table| table.signature() == signature);
+    let madt = find(Madt::SIGNATURE).and_then(|table| Madt::parse(table.data()));
+    let fadt = find(Fadt::SIGNATURE).and_then(|table| Fadt::parse(table.data()));
+    let hpet = find(Hpet::SIGNATURE).and_then(|table| Hpet::parse(table.data()));
+    // SAFETY: The address comes from a valid FADT.
+    let dsdt = fadt.and_then(|fadt| unsafe { Sdt::load(fadt.dsdt) }.ok());
+
+    TABLES.call_once(|| AcpiTables {
+        revision: rsdp.revision,
+        oem_id: rsdp.oem_id,
+        tables,
+        dsdt,
+        madt,
+        fadt,
+        hpet,
+    });
+
+    Ok(())
+}
+
+/// Returns the ACPI tables, if they were found.
+pub fn tables() -> Option<&'static AcpiTables> {
+    TABLES.get()
+}
+
+/// Returns `len` bytes of physical memory starting at `address`.
+///
+/// # Safety
+///
+/// The memory must not be modified while the slice exists.
+unsafe fn physical_bytes(address: PhysAddr, len: usize) -> &'static [u8] {
+    unsafe { slice::from_raw_parts(phys_to_virt(address).as_ptr(), len) }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn ascii(bytes: &[u8]) -> &str {
+    str::from_utf8(bytes).unwrap_or("?").trim_end()
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset + N)
+        .map(|bytes| bytes.try_into().unwrap())
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_tables_are_found() {
+        let tables = tables().expect("no ACPI tables");
+        assert!(tables.find("FACP").is_some());
+        assert!(tables.find("APIC").is_some());
+        assert!(tables.madt.is_some());
+        assert!(tables.fadt.is_some());
+    }
+
+    #[test_case]
+    fn test_checksum() {
+        assert_eq!(checksum(&[0x10, 0xf0]), 0);
+        assert_eq!(checksum(&[0xff, 0x02]), 1);
+    }
+
+    #[test_case]
+    fn test_generic_address() {
+        let data = [1, 8, 0, 1, 0x64, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            GenericAddress::parse(&data, 0),
+            Some(GenericAddress {
+                space: AddressSpaceId::SystemIo,
+                bit_width: 8,
+                bit_offset: 0,
+                access_size: 1,
+                address: 0x64,
+            })
+        );
+        assert_eq!(GenericAddress::parse(&data, 1), None);
+    }
+}
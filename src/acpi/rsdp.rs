@@ -0,0 +1,71 @@
+use x86_64::PhysAddr;
+
+use super::{ascii, checksum, physical_bytes, read_u16, read_u32, read_u64};
+
+const SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// Size of the RSDP in ACPI 1.0.
+const V1_SIZE: usize = 20;
+/// Size of the RSDP since ACPI 2.0.
+const V2_SIZE: usize = 36;
+
+/// Location of the real mode segment of the Extended BIOS Data Area.
+const EBDA_SEGMENT_POINTER: u64 = 0x40e;
+const EBDA_SEARCH_SIZE: u64 = 1024;
+const BIOS_AREA_START: u64 = 0xe_0000;
+const BIOS_AREA_END: u64 = 0x10_0000;
+
+/// Root System Description Pointer.
+#[derive(Debug, Clone, Copy)]
+pub struct Rsdp {
+    pub revision: u8,
+    pub oem_id: &'static str,
+    pub rsdt_address: PhysAddr,
+    /// Only available since ACPI 2.0.
+    pub xsdt_address: Option<PhysAddr>,
+}
+
+impl Rsdp {
+    fn parse(data: &'static [u8]) -> Option<Self> {
+        if &data[..8] != SIGNATURE || checksum(&data[..V1_SIZE]) != 0 {
+            return None;
+        }
+
+        let revision = data[15];
+        let xsdt_address = if revision >= 2 {
+            if checksum(&data[..V2_SIZE]) != 0 {
+                return None;
+            }
+            Some(PhysAddr::try_new(read_u64(data, 24)?).ok()?)
+        } else {
+            None
+        };
+
+        Some(Self {
+            revision,
+            oem_id: ascii(&data[9..15]),
+            rsdt_address: PhysAddr::try_new(u64::from(read_u32(data, 16)?)).ok()?,
+            xsdt_address,
+        })
+    }
+}
+
+/// Search the RSDP in the first KiB of the EBDA and in the BIOS ROM area.
+pub fn find() -> Option<Rsdp> {
+    // SAFETY: The BIOS data area is always present.
+    let ebda_segment = unsafe { physical_bytes(PhysAddr::new(EBDA_SEGMENT_POINTER), 2) };
+    let ebda_start = u64::from(read_u16(ebda_segment, 0)?) << 4;
+
+    let areas = [
+        (ebda_start, ebda_start + EBDA_SEARCH_SIZE),
+        (BIOS_AREA_START, BIOS_AREA_END),
+    ];
+    areas
+        .into_iter()
+        .filter(|&(start, _)| start != 0)
+        .flat_map(|(start, end)| (start..end - V2_SIZE as u64).step_by(16))
+        .find_map(|address| {
+            // SAFETY: The BIOS areas are never written by the kernel.
+            let data = unsafe { physical_bytes(PhysAddr::new(address), V2_SIZE) };
+            Rsdp::parse(data)
+        })
+}
@@ -0,0 +1,97 @@
+use core::alloc::Layout;
+use core::ptr;
+
+use super::{align_up, HeapAllocator};
+
+/// Allocator that hands out memory by bumping a pointer.
+///
+/// Memory is only reclaimed once all allocations are freed, at which point
+/// the whole heap becomes available again.
+pub struct BumpAllocator {
+    heap_start: usize,
+    heap_end: usize,
+    next: usize,
+    allocations: usize,
+}
+
+impl BumpAllocator {
+    /// Creates a new empty bump allocator.
+    pub const fn new() -> Self {
+        Self {
+            heap_start: 0,
+            heap_end: 0,
+            next: 0,
+            allocations: 0,
+        }
+    }
+}
+
+impl Default for BumpAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeapAllocator for BumpAllocator {
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_start = heap_start;
+        self.heap_end = heap_start + heap_size;
+        self.next = heap_start;
+    }
+
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let alloc_start = align_up(self.next, layout.align());
+        let alloc_end = match alloc_start.checked_add(layout.size()) {
+            Some(end) => end,
+            None => return ptr::null_mut(),
+        };
+
+        if alloc_end > self.heap_end {
+            ptr::null_mut()
+        } else {
+            self.next = alloc_end;
+            self.allocations += 1;
+            alloc_start as *mut u8
+        }
+    }
+
+    unsafe fn deallocate(&mut self, _ptr: *mut u8, _layout: Layout) {
+        self.allocations -= 1;
+        if self.allocations == 0 {
+            self.next = self.heap_start;
+        }
+    }
+
+    unsafe fn extend(&mut self, size: usize) {
+        self.heap_end += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::Region;
+    use super::*;
+
+    #[test_case]
+    fn test_reset_after_all_freed() {
+        let mut region = Region::new();
+        let start = region.start();
+        let mut allocator = BumpAllocator::new();
+        unsafe { allocator.init(start, Region::SIZE) };
+
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let first = allocator.allocate(layout);
+        let second = allocator.allocate(layout);
+        assert_eq!(first as usize, start);
+        assert_eq!(second as usize, start + 104);
+        assert!(allocator
+            .allocate(Layout::from_size_align(Region::SIZE, 8).unwrap())
+            .is_null());
+
+        unsafe {
+            allocator.deallocate(first, layout);
+            allocator.deallocate(second, layout);
+        }
+        assert_eq!(allocator.allocate(layout) as usize, start);
+    }
+}
@@ -0,0 +1,123 @@
+use core::alloc::Layout;
+use core::mem;
+
+use super::linked_list::LinkedListAllocator;
+use super::HeapAllocator;
+
+/// The block sizes to use.
+///
+/// The sizes must each be power of 2 because they are also used as
+/// the block alignment (alignments must be always powers of 2).
+const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+struct ListNode {
+    next: Option<&'static mut ListNode>,
+}
+
+/// Allocator that keeps a separate free list for each of the `BLOCK_SIZES`.
+///
+/// Allocations that do not fit into the largest block size are served by
+/// a linked list allocator, which also provides memory for new blocks.
+pub struct FixedSizeBlockAllocator {
+    list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
+    fallback_allocator: LinkedListAllocator,
+}
+
+impl FixedSizeBlockAllocator {
+    /// Creates an empty FixedSizeBlockAllocator.
+    pub const fn new() -> Self {
+        const EMPTY: Option<&'static mut ListNode> = None;
+        Self {
+            list_heads: [EMPTY; BLOCK_SIZES.len()],
+            fallback_allocator: LinkedListAllocator::new(),
+        }
+    }
+
+    /// Choose an appropriate block size for the given layout.
+    ///
+    /// Returns an index into the `BLOCK_SIZES` array.
+    fn list_index(layout: &Layout) -> Option<usize> {
+        let required_block_size = layout.size().max(layout.align());
+        BLOCK_SIZES.iter().position(|&s| s >= required_block_size)
+    }
+}
+
+impl Default for FixedSizeBlockAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeapAllocator for FixedSizeBlockAllocator {
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        unsafe { self.fallback_allocator.init(heap_start, heap_size) };
+    }
+
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        match Self::list_index(&layout) {
+            Some(index) => match self.list_heads[index].take() {
+                Some(node) => {
+                    self.list_heads[index] = node.next.take();
+                    node as *mut ListNode as *mut u8
+                }
+                None => {
+                    // no block exists in list => allocate new block
+                    let block_size = BLOCK_SIZES[index];
+                    // only works if all block sizes are a power of 2
+                    let block_align = block_size;
+                    let layout = Layout::from_size_align(block_size, block_align).unwrap();
+                    self.fallback_allocator.allocate(layout)
+                }
+            },
+            None => self.fallback_allocator.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        match Self::list_index(&layout) {
+            Some(index) => {
+                let new_node = ListNode {
+                    next: self.list_heads[index].take(),
+                };
+                // verify that block has size and alignment required for storing node
+                assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
+                assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
+                let new_node_ptr = ptr as *mut ListNode;
+                unsafe {
+                    new_node_ptr.write(new_node);
+                    self.list_heads[index] = Some(&mut *new_node_ptr);
+                }
+            }
+            None => unsafe { self.fallback_allocator.deallocate(ptr, layout) },
+        }
+    }
+
+    unsafe fn extend(&mut self, size: usize) {
+        unsafe { self.fallback_allocator.extend(size) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::Region;
+    use super::*;
+
+    #[test_case]
+    fn test_blocks_are_reused() {
+        let mut region = Region::new();
+        let start = region.start();
+        let mut allocator = FixedSizeBlockAllocator::new();
+        unsafe { allocator.init(start, Region::SIZE) };
+
+        let small = Layout::from_size_align(24, 8).unwrap();
+        let block = allocator.allocate(small);
+        assert!(!block.is_null());
+        assert_eq!(block as usize % 32, 0);
+        unsafe { allocator.deallocate(block, small) };
+        assert_eq!(allocator.allocate(small), block);
+
+        // too large for any block size, served by the fallback allocator
+        let large = Layout::from_size_align(2049, 8).unwrap();
+        assert!(!allocator.allocate(large).is_null());
+    }
+}
@@ -0,0 +1,218 @@
+use core::alloc::Layout;
+use core::{mem, ptr};
+
+use super::{align_up, HeapAllocator};
+
+struct ListNode {
+    size: usize,
+    next: Option<&'static mut ListNode>,
+}
+
+impl ListNode {
+    const fn new(size: usize) -> Self {
+        Self { size, next: None }
+    }
+
+    fn start_addr(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn end_addr(&self) -> usize {
+        self.start_addr() + self.size
+    }
+}
+
+/// Allocator that keeps all free regions in a singly linked list, which
+/// is stored inside of the free regions themselves.
+///
+/// The list is sorted by address, so that freed regions can be merged with
+/// their neighbours and the heap does not fragment over time.
+pub struct LinkedListAllocator {
+    head: ListNode,
+    heap_end: usize,
+}
+
+impl LinkedListAllocator {
+    /// Creates an empty LinkedListAllocator.
+    pub const fn new() -> Self {
+        Self {
+            head: ListNode::new(0),
+            heap_end: 0,
+        }
+    }
+
+    /// Adds the given memory region to the list, merging it with adjacent
+    /// free regions.
+    unsafe fn add_free_region(&mut self, addr: usize, size: usize) {
+        assert_eq!(align_up(addr, mem::align_of::<ListNode>()), addr);
+        assert!(size >= mem::size_of::<ListNode>());
+
+        // Find the last region which starts before the new one. The dummy
+        // head node has size 0, so it never gets merged with anything.
+        let mut current = &mut self.head;
+        while current
+            .next
+            .as_ref()
+            .is_some_and(|next| next.start_addr() < addr)
+        {
+            current = current.next.as_mut().unwrap();
+        }
+
+        if current.size > 0 && current.end_addr() == addr {
+            current.size += size;
+        } else {
+            let mut node = ListNode::new(size);
+            node.next = current.next.take();
+            let node_ptr = addr as *mut ListNode;
+            unsafe {
+                node_ptr.write(node);
+                current.next = Some(&mut *node_ptr);
+            }
+            current = current.next.as_mut().unwrap();
+        }
+
+        if let Some(next) = current.next.take() {
+            if current.end_addr() == next.start_addr() {
+                current.size += next.size;
+                current.next = next.next.take();
+            } else {
+                current.next = Some(next);
+            }
+        }
+    }
+
+    /// Looks for a free region with the given size and alignment and
+    /// removes it from the list.
+    ///
+    /// Returns a tuple of the list node and the start address of the allocation.
+    fn find_region(&mut self, size: usize, align: usize) -> Option<(&'static mut ListNode, usize)> {
+        let mut current = &mut self.head;
+
+        while let Some(ref mut region) = current.next {
+            if let Ok(alloc_start) = Self::alloc_from_region(region, size, align) {
+                let next = region.next.take();
+                let ret = Some((current.next.take().unwrap(), alloc_start));
+                current.next = next;
+                return ret;
+            } else {
+                current = current.next.as_mut().unwrap();
+            }
+        }
+
+        None
+    }
+
+    /// Try to use the given region for an allocation with given size and
+    /// alignment.
+    ///
+    /// Returns the allocation start address on success.
+    fn alloc_from_region(region: &ListNode, size: usize, align: usize) -> Result<usize, ()> {
+        let mut alloc_start = align_up(region.start_addr(), align);
+        let padding = alloc_start - region.start_addr();
+        if padding > 0 && padding < mem::size_of::<ListNode>() {
+            // padding in front of the allocation has to be able to hold
+            // a ListNode too, so that it can be given back to the list
+            alloc_start = align_up(region.start_addr() + mem::size_of::<ListNode>(), align);
+        }
+        let alloc_end = alloc_start.checked_add(size).ok_or(())?;
+
+        if alloc_end > region.end_addr() {
+            return Err(());
+        }
+
+        let excess_size = region.end_addr() - alloc_end;
+        if excess_size > 0 && excess_size < mem::size_of::<ListNode>() {
+            // rest of region too small to hold a ListNode (required because the
+            // allocation splits the region in a used and a free part)
+            return Err(());
+        }
+
+        Ok(alloc_start)
+    }
+
+    /// Adjust the given layout so that the resulting allocated memory
+    /// region is also capable of storing a `ListNode`.
+    ///
+    /// Returns the adjusted size and alignment as a (size, align) tuple.
+    fn size_align(layout: Layout) -> (usize, usize) {
+        let layout = layout
+            .align_to(mem::align_of::<ListNode>())
+            .expect("adjusting alignment failed")
+            .pad_to_align();
+        let size = layout.size().max(mem::size_of::<ListNode>());
+        (size, layout.align())
+    }
+}
+
+impl Default for LinkedListAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeapAllocator for LinkedListAllocator {
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        unsafe { self.add_free_region(heap_start, heap_size) };
+        self.heap_end = heap_start + heap_size;
+    }
+
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Self::size_align(layout);
+
+        match self.find_region(size, align) {
+            Some((region, alloc_start)) => {
+                let region_start = region.start_addr();
+                let region_end = region.end_addr();
+                let alloc_end = alloc_start.checked_add(size).expect("overflow");
+
+                if alloc_start > region_start {
+                    unsafe { self.add_free_region(region_start, alloc_start - region_start) };
+                }
+                if region_end > alloc_end {
+                    unsafe { self.add_free_region(alloc_end, region_end - alloc_end) };
+                }
+                alloc_start as *mut u8
+            }
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Self::size_align(layout);
+
+        unsafe { self.add_free_region(ptr as usize, size) }
+    }
+
+    unsafe fn extend(&mut self, size: usize) {
+        unsafe { self.add_free_region(self.heap_end, size) };
+        self.heap_end += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::Region;
+    use super::*;
+
+    #[test_case]
+    fn test_freed_regions_are_merged() {
+        let mut region = Region::new();
+        let start = region.start();
+        let mut allocator = LinkedListAllocator::new();
+        unsafe { allocator.init(start, Region::SIZE) };
+
+        let half = Layout::from_size_align(Region::SIZE / 2, 8).unwrap();
+        let first = allocator.allocate(half);
+        let second = allocator.allocate(half);
+        assert!(!first.is_null() && !second.is_null());
+        assert!(allocator.allocate(half).is_null());
+
+        // freed in the opposite order, so both neighbours have to be merged
+        unsafe {
+            allocator.deallocate(second, half);
+            allocator.deallocate(first, half);
+        }
+        let whole = Layout::from_size_align(Region::SIZE, 8).unwrap();
+        assert_eq!(allocator.allocate(whole) as usize, start);
+    }
+}
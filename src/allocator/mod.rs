@@ -0,0 +1,240 @@
+use core::alloc::{GlobalAlloc, Layout};
+
+use spin::{Mutex, MutexGuard};
+use x86_64::{
+    instructions::interrupts::without_interrupts, structures::paging::PageTableFlags, VirtAddr,
+};
+
+use crate::mem::paging::{self, PagingError};
+
+pub mod bump;
+pub mod fixed_size_block;
+pub mod linked_list;
+
+#[cfg(any(
+    all(feature = "bump_allocator", feature = "linked_list_allocator"),
+    all(feature = "bump_allocator", feature = "fixed_size_block_allocator"),
+    all(
+        feature = "linked_list_allocator",
+        feature = "fixed_size_block_allocator"
+    ),
+))]
+compile_error!("only one heap allocator feature can be enabled at a time");
+
+#[cfg(not(any(
+    feature = "bump_allocator",
+    feature = "linked_list_allocator",
+    feature = "fixed_size_block_allocator",
+)))]
+compile_error!("one of the heap allocator features has to be enabled");
+
+#[cfg(feature = "bump_allocator")]
+type SelectedAllocator = bump::BumpAllocator;
+#[cfg(feature = "linked_list_allocator")]
+type SelectedAllocator = linked_list::LinkedListAllocator;
+#[cfg(feature = "fixed_size_block_allocator")]
+type SelectedAllocator = fixed_size_block::FixedSizeBlockAllocator;
+
+pub const HEAP_START: usize = 0x_4444_4444_0000;
+pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
+/// Upper limit the heap is allowed to grow to.
+pub const HEAP_MAX_SIZE: usize = 16 * 1024 * 1024; // 16 MiB
+/// The heap always grows by at least this many bytes at once.
+const HEAP_GROWTH_MIN: usize = 64 * 1024; // 64 KiB
+const PAGE_SIZE: usize = 4096;
+
+#[global_allocator]
+static ALLOCATOR: Locked<Heap<SelectedAllocator>> =
+    Locked::new(Heap::new(SelectedAllocator::new()));
+
+/// Map the initial heap region and hand it over to the global allocator.
+pub fn init_heap() -> Result<(), PagingError> {
+    map_heap_pages(HEAP_START, HEAP_SIZE)?;
+
+    // SAFETY: The heap region has just been mapped and is used by nothing else.
+    unsafe { ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE) };
+
+    Ok(())
+}
+
+/// Returns the current size of the heap in bytes.
+pub fn heap_size() -> usize {
+    without_interrupts(|| ALLOCATOR.lock().size)
+}
+
+/// Map fresh frames for the heap region `[start, start + size)`.
+fn map_heap_pages(start: usize, size: usize) -> Result<(), PagingError> {
+    let pages = paging::page_range(VirtAddr::new(start as u64), size as u64);
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+    paging::with_kernel_space(|space| space.map_range(pages, flags))
+}
+
+#[alloc_error_handler]
+fn alloc_error_handler(layout: Layout) -> ! {
+    panic!(
+        "allocation error: {:?} (heap size: {} bytes, limit: {} bytes)",
+        layout,
+        heap_size(),
+        HEAP_MAX_SIZE
+    )
+}
+
+/// A heap allocator together with the bookkeeping needed to grow it.
+///
+/// The heap occupies `[HEAP_START, HEAP_START + size)`. When the allocator
+/// runs out of memory, new pages are mapped right after the end of the
+/// heap, until `HEAP_MAX_SIZE` is reached.
+pub struct Heap<A> {
+    allocator: A,
+    size: usize,
+}
+
+impl<A: HeapAllocator> Heap<A> {
+    pub const fn new(allocator: A) -> Self {
+        Self { allocator, size: 0 }
+    }
+
+    /// # Safety
+    ///
+    /// Same as for [`HeapAllocator::init`].
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        unsafe { self.allocator.init(heap_start, heap_size) };
+        self.size = heap_size;
+    }
+
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        loop {
+            let ptr = self.allocator.allocate(layout);
+            if !ptr.is_null() {
+                return ptr;
+            }
+            if !self.grow(layout.size() + layout.align()) {
+                return ptr;
+            }
+        }
+    }
+
+    /// Grow the heap by at least `min_size` bytes.
+    ///
+    /// Returns `false` if the heap limit was reached or there is no
+    /// physical memory left.
+    fn grow(&mut self, min_size: usize) -> bool {
+        let remaining = HEAP_MAX_SIZE - self.size;
+        if min_size > remaining {
+            return false;
+        }
+        let size = align_up(min_size.max(HEAP_GROWTH_MIN), PAGE_SIZE).min(remaining);
+
+        if map_heap_pages(HEAP_START + self.size, size).is_err() {
+            return false;
+        }
+
+        // SAFETY: The pages right after the heap have just been mapped.
+        unsafe { self.allocator.extend(size) };
+        self.size += size;
+
+        true
+    }
+}
+
+/// Interface shared by all heap allocator designs.
+///
+/// Allocators implementing it don't have to care about synchronization,
+/// it is provided by wrapping them in [`Locked`].
+pub trait HeapAllocator {
+    /// Initialize the allocator with the given heap bounds.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that the given heap bounds are valid and
+    /// that the heap is unused. This method must be called only once.
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize);
+
+    /// Allocate memory described by `layout`.
+    ///
+    /// Returns a null pointer if the request can't be satisfied.
+    fn allocate(&mut self, layout: Layout) -> *mut u8;
+
+    /// Deallocate memory previously returned by [`HeapAllocator::allocate`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated by this allocator with the same `layout`.
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout);
+
+    /// Add `size` bytes directly after the current end of the heap.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that the added memory is valid and unused.
+    unsafe fn extend(&mut self, size: usize);
+}
+
+/// A wrapper around `spin::Mutex` that allows implementing foreign traits
+/// (like `GlobalAlloc`) for allocator types.
+pub struct Locked<A> {
+    inner: Mutex<A>,
+}
+
+impl<A> Locked<A> {
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, A> {
+        self.inner.lock()
+    }
+}
+
+unsafe impl<A: HeapAllocator> GlobalAlloc for Locked<Heap<A>> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        without_interrupts(|| self.lock().allocate(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        without_interrupts(|| unsafe { self.lock().allocator.deallocate(ptr, layout) })
+    }
+}
+
+/// Align the given address `addr` upwards to alignment `align`.
+///
+/// Requires that `align` is a power of two.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+
+    /// Memory for testing an allocator on its own, outside of the kernel
+    /// heap.
+    #[repr(align(4096))]
+    pub(super) struct Region(pub [u8; Region::SIZE]);
+
+    impl Region {
+        pub const SIZE: usize = 4096;
+
+        pub fn new() -> Box<Self> {
+            Box::new(Self([0; Self::SIZE]))
+        }
+
+        pub fn start(&mut self) -> usize {
+            self.0.as_mut_ptr() as usize
+        }
+    }
+
+    #[test_case]
+    fn test_string_and_btree_map() {
+        let mut map = BTreeMap::new();
+        for i in 0..100 {
+            map.insert(i, alloc::format!("value {}", i));
+        }
+        assert_eq!(map.get(&42).map(String::as_str), Some("value 42"));
+    }
+}
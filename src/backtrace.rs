@@ -0,0 +1,149 @@
+use core::arch::asm;
+use core::fmt;
+
+use x86_64::VirtAddr;
+
+use crate::interrupts::trap::TrapFrame;
+use crate::mem::paging;
+use crate::symbols;
+use crate::{serial_println, vga_println};
+
+/// Maximum number of return addresses recorded in a [`Backtrace`].
+pub const MAX_FRAMES: usize = 16;
+
+/// Return addresses collected by walking the chain of saved frame pointers.
+///
+/// This relies on the kernel being built with frame pointers (see
+/// `frame-pointer` in the target specification). Every frame starts with
+/// the caller's `rbp`, followed by the return address.
+#[derive(Debug, Clone, Copy)]
+pub struct Backtrace {
+    addresses: [u64; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// Capture the backtrace of the caller.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        let rbp: u64;
+        unsafe {
+            asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags));
+        }
+        Self::walk(None, rbp)
+    }
+
+    /// Capture the backtrace of the code interrupted by an exception,
+    /// starting with the faulting instruction.
+    pub fn from_trap_frame(frame: &TrapFrame) -> Self {
+        Self::walk(Some(frame.rip), frame.rbp)
+    }
+
+    /// Returns the recorded return addresses, innermost first.
+    pub fn addresses(&self) -> &[u64] {
+        &self.addresses[..self.len]
+    }
+
+    fn walk(first: Option<u64>, mut rbp: u64) -> Self {
+        let mut backtrace = Self {
+            addresses: [0; MAX_FRAMES],
+            len: 0,
+        };
+        if let Some(address) = first {
+            backtrace.push(address);
+        }
+
+        while backtrace.len < MAX_FRAMES {
+            // The frame pointer may be garbage (e.g. in code entered from
+            // the bootloader), so it is checked before being dereferenced.
+            if rbp == 0 || !rbp.is_multiple_of(8) || !is_readable(rbp) || !is_readable(rbp + 8) {
+                break;
+            }
+
+            let frame = rbp as *const u64;
+            // SAFETY: Both words were checked to be mapped above.
+            let (next, return_address) = unsafe { (frame.read(), frame.add(1).read()) };
+            if return_address == 0 {
+                break;
+            }
+            backtrace.push(return_address);
+
+            // The stack grows down, so callers' frames are at higher
+            // addresses. Anything else means the chain is corrupted.
+            if next <= rbp {
+                break;
+            }
+            rbp = next;
+        }
+
+        backtrace
+    }
+
+    fn push(&mut self, address: u64) {
+        self.addresses[self.len] = address;
+        self.len += 1;
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Backtrace:")?;
+        if self.len == 0 {
+            write!(f, "\n  <unavailable>")?;
+        }
+        for (i, &address) in self.addresses().iter().enumerate() {
+            write!(f, "\n  {:>2}: {:#018x}", i, address)?;
+            // A return address points after the call, which may already be
+            // the start of the next function if the call never returns.
+            if let Some(symbol) = symbols::lookup(address.saturating_sub(1)) {
+                write!(f, " {}+{:#x}", symbol.name, address - symbol.address)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_readable(addr: u64) -> bool {
+    VirtAddr::try_new(addr).is_ok_and(paging::is_mapped)
+}
+
+/// Print the backtrace both on the screen and to the serial port.
+pub fn print(backtrace: &Backtrace) {
+    vga_println!("{}", backtrace);
+    serial_println!("{}", backtrace);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn nested(depth: usize) -> Backtrace {
+        if depth == 0 {
+            Backtrace::capture()
+        } else {
+            let backtrace = nested(depth - 1);
+            // prevent tail call optimization
+            core::hint::black_box(depth);
+            backtrace
+        }
+    }
+
+    #[test_case]
+    fn test_capture_walks_nested_calls() {
+        let backtrace = nested(3);
+        assert!(backtrace.addresses().len() >= 4);
+
+        // the three recursive calls return to the same place
+        let addresses = backtrace.addresses();
+        assert_eq!(addresses[0], addresses[1]);
+        assert_eq!(addresses[1], addresses[2]);
+        assert_ne!(addresses[2], addresses[3]);
+    }
+
+    #[test_case]
+    fn test_invalid_frame_pointer() {
+        let backtrace = Backtrace::walk(Some(0x1234), 0xdead_beef);
+        assert_eq!(backtrace.addresses(), &[0x1234]);
+    }
+}
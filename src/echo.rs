@@ -1,10 +1,15 @@
+use alloc::string::String;
 use core::fmt::Write;
 
+use futures_util::stream::StreamExt;
 use lazy_static::lazy_static;
+use pc_keyboard::DecodedKey;
 use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
 
 use crate::io::vga::{Color, ColorCode, WRITER};
-use crate::vga_print;
+use crate::task::keyboard::KeyStream;
+use crate::{vga_print, vga_println};
 
 const BUFFER_SIZE: usize = 75;
 
@@ -55,6 +60,10 @@ lazy_static! {
 
 pub fn init() {
     let mut writer = WRITER.lock();
+    if let Some(boot_time) = crate::time::system::boot_time() {
+        writeln!(writer, "Booted at {} UTC\n", boot_time.date_time()).unwrap();
+    }
+
     let light_blue = ColorCode::new_with_black_background(Color::LightBlue);
     let old_color = writer.set_color(light_blue);
 
@@ -68,25 +77,161 @@ pub fn init() {
     write!(writer, "> ").unwrap();
 }
 
+/// A command understood by the echo console.
+pub struct Command {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub run: fn(),
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        help: "list the available commands",
+        run: help,
+    },
+    Command {
+        name: "acpi",
+        help: "dump the ACPI tables",
+        run: acpi,
+    },
+    Command {
+        name: "irqs",
+        help: "show the interrupt counters",
+        run: irqs,
+    },
+    Command {
+        name: "uptime",
+        help: "show the time since boot",
+        run: uptime,
+    },
+    Command {
+        name: "date",
+        help: "show the current date and time",
+        run: date,
+    },
+    Command {
+        name: "clock",
+        help: "show the TSC frequency and timer source",
+        run: clock,
+    },
+    Command {
+        name: "shutdown",
+        help: "turn the machine off",
+        run: || crate::power::shutdown(),
+    },
+    Command {
+        name: "reboot",
+        help: "restart the machine",
+        run: || crate::power::reboot(),
+    },
+];
+
+fn help() {
+    for command in COMMANDS {
+        vga_println!("{:>10}  {}", command.name, command.help);
+    }
+}
+
+fn acpi() {
+    match crate::acpi::tables() {
+        Some(tables) => vga_println!("{}", tables),
+        None => vga_println!("no ACPI tables found"),
+    }
+}
+
+fn irqs() {
+    use crate::interrupts::irq;
+
+    for line in 0..irq::IRQ_LINES {
+        let handlers = irq::handlers(line);
+        let count = irq::interrupt_count(irq::vector(line));
+        if !handlers.is_empty() || count != 0 {
+            vga_println!("IRQ {:>2}: {:>10}  {}", line, count, handlers.join(", "));
+        }
+    }
+    vga_println!("NMIs: {}", irq::interrupt_count(2));
+    vga_println!(
+        "deferred work dropped: {}",
+        crate::interrupts::deferred::dropped()
+    );
+    vga_println!("scancodes dropped: {}", crate::io::keyboard::overflows());
+}
+
+fn uptime() {
+    let uptime = crate::time::uptime();
+    vga_println!(
+        "up {}.{:03}s ({} ticks)",
+        uptime.as_secs(),
+        uptime.subsec_millis(),
+        crate::time::ticks()
+    );
+}
+
+fn date() {
+    vga_println!("{} UTC", crate::time::SystemTime::now());
+}
+
+fn clock() {
+    use crate::time::tsc;
+
+    match tsc::frequency() {
+        Some(frequency) => vga_println!(
+            "TSC: {}.{:03} MHz, calibrated against {:?}, {}",
+            frequency / 1_000_000,
+            frequency / 1_000 % 1_000,
+            tsc::reference().unwrap(),
+            if tsc::is_invariant() {
+                "invariant"
+            } else {
+                "not invariant"
+            }
+        ),
+        None => vga_println!("TSC not calibrated"),
+    }
+    if let Some(source) = crate::time::source() {
+        vga_println!(
+            "timer: {:?}, tick {:?}",
+            source,
+            crate::time::tick_duration()
+        );
+    }
+}
+
+/// Returns the command the line consists of, if any.
+fn find_command(line: &[char]) -> Option<&'static Command> {
+    let line: String = line.iter().collect();
+    COMMANDS.iter().find(|command| command.name == line.trim())
+}
+
+/// Echo the keys typed on the keyboard, forever.
+///
+/// # Panics
+///
+/// If something else already consumes the keyboard input.
+pub async fn run() {
+    let mut keys = KeyStream::new();
+    while let Some(key) = keys.next().await {
+        if let DecodedKey::Unicode(character) = key {
+            process(character);
+        }
+    }
+}
+
+/// Handle a character typed on the keyboard.
 pub fn process(c: char) {
     match c {
         '\n' => {
             let buff = DEFAULT_ECHO.lock().clear();
-            let mut writer = WRITER.lock();
-
-            write!(writer, "\n").unwrap();
-            let light_blue = ColorCode::new_with_black_background(Color::LightBlue);
-            let old_color = writer.set_color(light_blue);
-            write!(writer, "@ ").unwrap();
-
-            let mut char_buffer = [0; 4];
-            for c in buff.data() {
-                let c = c.encode_utf8(&mut char_buffer);
-                writer.write_string(c);
+            if let Some(command) = find_command(buff.data()) {
+                vga_print!("\n");
+                (command.run)();
+                vga_print!("\n> ");
+                return;
             }
 
-            writer.set_color(old_color);
-            write!(writer, "\n\n> ").unwrap();
+            // the writer is also used by interrupt handlers
+            without_interrupts(|| echo_line(&buff));
         }
         c => {
             DEFAULT_ECHO.lock().push(c);
@@ -94,3 +239,21 @@ pub fn process(c: char) {
         }
     }
 }
+
+fn echo_line(buff: &EchoBuffer) {
+    let mut writer = WRITER.lock();
+
+    write!(writer, "\n").unwrap();
+    let light_blue = ColorCode::new_with_black_background(Color::LightBlue);
+    let old_color = writer.set_color(light_blue);
+    write!(writer, "@ ").unwrap();
+
+    let mut char_buffer = [0; 4];
+    for c in buff.data() {
+        let c = c.encode_utf8(&mut char_buffer);
+        writer.write_string(c);
+    }
+
+    writer.set_color(old_color);
+    write!(writer, "\n\n> ").unwrap();
+}
@@ -11,25 +11,63 @@ use x86_64::{
     VirtAddr,
 };
 
+use crate::mem::stack;
+
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+pub const NMI_IST_INDEX: u16 = 1;
+pub const MACHINE_CHECK_IST_INDEX: u16 = 2;
+pub const PAGE_FAULT_IST_INDEX: u16 = 3;
+
+/// Configuration of a single interrupt stack.
+struct IstStack {
+    index: u16,
+    pages: u64,
+}
+
+/// Interrupt stacks to allocate. The TSS has room for up to 7 of them.
+const IST_STACKS: &[IstStack] = &[
+    IstStack {
+        index: DOUBLE_FAULT_IST_INDEX,
+        pages: 5,
+    },
+    IstStack {
+        index: NMI_IST_INDEX,
+        pages: 2,
+    },
+    IstStack {
+        index: MACHINE_CHECK_IST_INDEX,
+        pages: 2,
+    },
+    IstStack {
+        index: PAGE_FAULT_IST_INDEX,
+        pages: 5,
+    },
+];
 
 lazy_static! {
     static ref TSS: TaskStateSegment = {
         let mut tss = TaskStateSegment::new();
-        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
-            const STACK_SIZE: usize = 4096 * 5;
-            static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
-
-            let stack_start = VirtAddr::from_ptr(unsafe { &STACK });
-
-            #[allow(clippy::let_and_return)]
-            let stack_end = stack_start + STACK_SIZE;
-            stack_end
-        };
+        for ist in IST_STACKS {
+            tss.interrupt_stack_table[ist.index as usize] = allocate_ist_stack(ist.pages);
+        }
         tss
     };
 }
 
+/// Returns the top of the double fault stack.
+pub fn double_fault_stack_top() -> VirtAddr {
+    TSS.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize]
+}
+
+/// Allocate a guard-paged stack for an IST entry and return its top.
+///
+/// The stack is never freed, since the TSS lives as long as the kernel.
+fn allocate_ist_stack(pages: u64) -> VirtAddr {
+    stack::allocate_stack(pages)
+        .expect("failed to allocate an interrupt stack")
+        .top()
+}
+
 lazy_static! {
     static ref GDT: (GlobalDescriptorTable, Selectors) = {
         let mut gdt = GlobalDescriptorTable::new();
@@ -50,6 +88,10 @@ struct Selectors {
     tss_selector: SegmentSelector,
 }
 
+/// Load the GDT and the TSS.
+///
+/// Memory management has to be initialized first, since the interrupt
+/// stacks are allocated at runtime.
 pub fn init() {
     let (
         gdt,
@@ -0,0 +1,339 @@
+use core::arch::x86_64::__cpuid;
+
+use spin::{Mutex, Once};
+use x86_64::{registers::model_specific::Msr, PhysAddr};
+
+use crate::acpi::{
+    self,
+    madt::{Madt, Polarity, TriggerMode},
+};
+use crate::mem::mmio::{self, MmioError, MmioRegion};
+
+const IA32_APIC_BASE_MSR: u32 = 0x1b;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+const APIC_BASE_ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+const CPUID_FEATURES_EDX_APIC: u32 = 1 << 9;
+
+/// Physical address of the I/O APIC on virtually every PC, used if there
+/// are no ACPI tables.
+const DEFAULT_IO_APIC_ADDRESS: u64 = 0xfec0_0000;
+
+/// Vector the local APIC delivers spurious interrupts to.
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+const LOCAL_APIC_SIZE: u64 = 0x400;
+const IO_APIC_SIZE: u64 = 0x20;
+
+/// Local APIC register offsets.
+mod local {
+    pub const ID: u64 = 0x20;
+    pub const VERSION: u64 = 0x30;
+    pub const TASK_PRIORITY: u64 = 0x80;
+    pub const END_OF_INTERRUPT: u64 = 0xb0;
+    pub const SPURIOUS_VECTOR: u64 = 0xf0;
+    pub const LVT_TIMER: u64 = 0x320;
+    pub const LVT_ERROR: u64 = 0x370;
+
+    pub const SOFTWARE_ENABLE: u32 = 1 << 8;
+    pub const LVT_MASKED: u32 = 1 << 16;
+}
+
+/// I/O APIC register offsets and indices.
+mod io {
+    pub const REGISTER_SELECT: u64 = 0x00;
+    pub const REGISTER_WINDOW: u64 = 0x10;
+
+    pub const ID: u32 = 0x00;
+    pub const VERSION: u32 = 0x01;
+    pub const REDIRECTION_TABLE: u32 = 0x10;
+
+    pub const REDIRECTION_ACTIVE_LOW: u32 = 1 << 13;
+    pub const REDIRECTION_LEVEL_TRIGGERED: u32 = 1 << 15;
+    pub const REDIRECTION_MASKED: u32 = 1 << 16;
+}
+
+/// Errors returned when setting up the APIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicError {
+    /// The CPU has no local APIC.
+    Unsupported,
+    Mmio(MmioError),
+    /// There is no I/O APIC at the expected address.
+    NoIoApic(PhysAddr),
+}
+
+/// The local APIC of the CPU, which receives the interrupts routed by the
+/// I/O APIC.
+pub struct LocalApic {
+    mmio: MmioRegion,
+}
+
+impl LocalApic {
+    pub fn id(&self) -> u8 {
+        (self.mmio.read_u32(local::ID) >> 24) as u8
+    }
+
+    pub fn version(&self) -> u8 {
+        self.mmio.read_u32(local::VERSION) as u8
+    }
+
+    pub fn end_of_interrupt(&self) {
+        self.mmio.write_u32(local::END_OF_INTERRUPT, 0);
+    }
+
+    fn enable(&self) {
+        self.mmio.write_u32(local::LVT_TIMER, local::LVT_MASKED);
+        self.mmio.write_u32(local::LVT_ERROR, local::LVT_MASKED);
+        self.mmio.write_u32(local::TASK_PRIORITY, 0);
+        self.mmio.write_u32(
+            local::SPURIOUS_VECTOR,
+            local::SOFTWARE_ENABLE | u32::from(SPURIOUS_VECTOR),
+        );
+    }
+}
+
+/// The I/O APIC, which routes device interrupts to local APICs.
+pub struct IoApic {
+    mmio: MmioRegion,
+}
+
+impl IoApic {
+    pub fn id(&mut self) -> u8 {
+        ((self.read(io::ID) >> 24) & 0xf) as u8
+    }
+
+    /// Returns the number of interrupt inputs.
+    pub fn redirection_entries(&mut self) -> u8 {
+        ((self.read(io::VERSION) >> 16) & 0xff) as u8 + 1
+    }
+
+    /// Deliver the interrupt input `gsi` as `vector` to the local APIC
+    /// with the given ID.
+    ///
+    /// Conforming polarity and trigger mode mean active high and edge
+    /// triggered, as on the ISA bus.
+    pub fn route(
+        &mut self,
+        gsi: u8,
+        vector: u8,
+        apic_id: u8,
+        polarity: Polarity,
+        trigger_mode: TriggerMode,
+    ) {
+        let index = io::REDIRECTION_TABLE + u32::from(gsi) * 2;
+        self.write(index + 1, u32::from(apic_id) << 24);
+        self.write(index, redirection_entry(vector, polarity, trigger_mode));
+    }
+
+    pub fn mask(&mut self, gsi: u8) {
+        let index = io::REDIRECTION_TABLE + u32::from(gsi) * 2;
+        let entry = self.read(index);
+        self.write(index, entry | io::REDIRECTION_MASKED);
+    }
+
+    /// Returns the vector `gsi` is delivered as, if it isn't masked.
+    pub fn vector(&mut self, gsi: u8) -> Option<u8> {
+        let entry = self.read(io::REDIRECTION_TABLE + u32::from(gsi) * 2);
+        (entry & io::REDIRECTION_MASKED == 0).then_some(entry as u8)
+    }
+
+    fn read(&mut self, register: u32) -> u32 {
+        self.mmio.write_u32(io::REGISTER_SELECT, register);
+        self.mmio.read_u32(io::REGISTER_WINDOW)
+    }
+
+    fn write(&mut self, register: u32, value: u32) {
+        self.mmio.write_u32(io::REGISTER_SELECT, register);
+        self.mmio.write_u32(io::REGISTER_WINDOW, value);
+    }
+}
+
+static LOCAL_APIC: Once<LocalApic> = Once::new();
+static IO_APIC: Once<Mutex<IoApic>> = Once::new();
+
+/// Returns whether the CPU has a local APIC.
+pub fn is_supported() -> bool {
+    __cpuid(1).edx & CPUID_FEATURES_EDX_APIC != 0
+}
+
+/// Returns the low half of a redirection table entry, which isn't masked.
+fn redirection_entry(vector: u8, polarity: Polarity, trigger_mode: TriggerMode) -> u32 {
+    let mut entry = u32::from(vector);
+    if polarity == Polarity::ActiveLow {
+        entry |= io::REDIRECTION_ACTIVE_LOW;
+    }
+    if trigger_mode == TriggerMode::Level {
+        entry |= io::REDIRECTION_LEVEL_TRIGGERED;
+    }
+    entry
+}
+
+/// Returns the global system interrupt a legacy ISA IRQ is connected to,
+/// with the polarity and trigger mode of the MADT interrupt override.
+///
+/// Without the ACPI tables we assume the usual wiring, where only the PIT
+/// (IRQ 0) is connected to another input than its own.
+fn isa_irq_route(madt: Option<&Madt>, irq: u8) -> (u8, Polarity, TriggerMode) {
+    let interrupt_override = madt.and_then(|madt| madt.overrides.iter().find(|o| o.irq == irq));
+    match (madt, interrupt_override) {
+        (_, Some(o)) => (o.gsi as u8, o.polarity, o.trigger_mode),
+        (None, None) if irq == 0 => (2, Polarity::Conforming, TriggerMode::Conforming),
+        _ => (irq, Polarity::Conforming, TriggerMode::Conforming),
+    }
+}
+
+fn madt() -> Option<&'static Madt> {
+    acpi::tables().and_then(|tables| tables.madt.as_ref())
+}
+
+/// Returns the address of the I/O APIC handling the ISA IRQs.
+fn io_apic_address() -> PhysAddr {
+    acpi::tables()
+        .and_then(|tables| tables.madt.as_ref())
+        .and_then(|madt| madt.io_apics.iter().find(|io_apic| io_apic.gsi_base == 0))
+        .map_or(PhysAddr::new(DEFAULT_IO_APIC_ADDRESS), |io_apic| {
+            io_apic.address
+        })
+}
+
+/// Enable the local APIC and the I/O APIC, with all inputs masked.
+///
+/// IRQs are routed with [`route_irq`]. The 8259 PIC has to be masked
+/// afterwards.
+pub fn init() -> Result<(), ApicError> {
+    if !is_supported() {
+        return Err(ApicError::Unsupported);
+    }
+
+    let io_base = io_apic_address();
+    // SAFETY: The address belongs to the I/O APIC and not to RAM.
+    let io_mmio = unsafe { mmio::map(io_base, IO_APIC_SIZE) }.map_err(ApicError::Mmio)?;
+    let mut io_apic = IoApic { mmio: io_mmio };
+    // reads of a non existent device return all ones
+    if io_apic.read(io::VERSION) == u32::MAX {
+        mmio::unmap(io_apic.mmio).map_err(ApicError::Mmio)?;
+        return Err(ApicError::NoIoApic(io_base));
+    }
+
+    let mut base_msr = Msr::new(IA32_APIC_BASE_MSR);
+    // SAFETY: The MSR exists, since the CPU has an APIC. Only the enable
+    // bit is changed.
+    let base = unsafe {
+        let base = base_msr.read() | APIC_BASE_ENABLE;
+        base_msr.write(base);
+        PhysAddr::new(base & APIC_BASE_ADDRESS_MASK)
+    };
+    // SAFETY: The address belongs to the local APIC and not to RAM.
+    let local_mmio = unsafe { mmio::map(base, LOCAL_APIC_SIZE) }.map_err(ApicError::Mmio)?;
+    let local_apic = LOCAL_APIC.call_once(|| LocalApic { mmio: local_mmio });
+    local_apic.enable();
+
+    for gsi in 0..io_apic.redirection_entries() {
+        io_apic.mask(gsi);
+    }
+    IO_APIC.call_once(|| Mutex::new(io_apic));
+
+    Ok(())
+}
+
+/// Returns the local APIC, if it was set up.
+pub fn local_apic() -> Option<&'static LocalApic> {
+    LOCAL_APIC.get()
+}
+
+/// Returns the I/O APIC, if it was set up.
+pub fn io_apic() -> Option<&'static Mutex<IoApic>> {
+    IO_APIC.get()
+}
+
+/// Deliver the legacy ISA IRQ `irq` as `vector` to this CPU.
+///
+/// # Panics
+///
+/// If the APIC is not initialized.
+pub fn route_irq(irq: u8, vector: u8) {
+    let apic_id = local_apic()
+        .expect("the local APIC is not initialized")
+        .id();
+    let (gsi, polarity, trigger_mode) = isa_irq_route(madt(), irq);
+    io_apic()
+        .expect("the I/O APIC is not initialized")
+        .lock()
+        .route(gsi, vector, apic_id, polarity, trigger_mode);
+}
+
+/// Stop delivering the legacy ISA IRQ `irq`.
+///
+/// # Panics
+///
+/// If the APIC is not initialized.
+pub fn mask_irq(irq: u8) {
+    io_apic()
+        .expect("the I/O APIC is not initialized")
+        .lock()
+        .mask(isa_irq_route(madt(), irq).0);
+}
+
+/// Signal the end of the interrupt currently being handled.
+pub fn end_of_interrupt() {
+    local_apic()
+        .expect("the local APIC is not initialized")
+        .end_of_interrupt();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{controller, Controller, InterruptIndex};
+    use super::*;
+    use crate::acpi::madt::tests::MADT;
+
+    #[test_case]
+    fn test_apic_is_used() {
+        assert!(is_supported());
+        assert_eq!(controller(), Some(Controller::Apic));
+    }
+
+    #[test_case]
+    fn test_local_apic_version() {
+        let version = local_apic().expect("no local APIC").version();
+        // integrated APICs have versions 0x10 to 0x15
+        assert!((0x10..=0x15).contains(&version));
+    }
+
+    #[test_case]
+    fn test_redirection_entry() {
+        assert_eq!(
+            redirection_entry(0x21, Polarity::Conforming, TriggerMode::Conforming),
+            0x21
+        );
+        assert_eq!(
+            redirection_entry(0x29, Polarity::ActiveLow, TriggerMode::Level),
+            0x29 | 1 << 13 | 1 << 15
+        );
+    }
+
+    #[test_case]
+    fn test_isa_irq_route_from_madt() {
+        let madt = Madt::parse(MADT).expect("parsing failed");
+
+        let (gsi, polarity, trigger_mode) = isa_irq_route(Some(&madt), 9);
+        assert_eq!(gsi, 9);
+        let entry = redirection_entry(0x29, polarity, trigger_mode);
+        assert_eq!(entry, 0x29 | io::REDIRECTION_LEVEL_TRIGGERED);
+
+        let (gsi, polarity, trigger_mode) = isa_irq_route(Some(&madt), 0);
+        assert_eq!(gsi, 2);
+        assert_eq!(redirection_entry(0x20, polarity, trigger_mode), 0x20);
+        assert_eq!(isa_irq_route(Some(&madt), 1).0, 1);
+        assert_eq!(isa_irq_route(None, 0).0, 2);
+    }
+
+    #[test_case]
+    fn test_io_apic_routes() {
+        let mut io_apic = io_apic().expect("no I/O APIC").lock();
+        assert!(io_apic.redirection_entries() >= 16);
+        assert_eq!(io_apic.vector(1), Some(InterruptIndex::Keyboard.as_u8()));
+        assert_eq!(io_apic.vector(2), Some(InterruptIndex::Timer.as_u8()));
+        assert_eq!(io_apic.vector(3), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Work deferred by IRQ handlers, which runs after the end of interrupt
+//! with interrupts enabled.
+//!
+//! Handlers should only acknowledge their device and leave everything
+//! else, like decoding data or updating the screen, to a work item queued
+//! with [`defer`].
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use x86_64::instructions::interrupts;
+
+use crate::ring_buffer::RingBuffer;
+
+/// Maximum number of pending work items.
+const QUEUE_SIZE: usize = 64;
+
+/// A function to call later, with its argument.
+#[derive(Debug, Clone, Copy)]
+pub struct Work {
+    pub function: fn(usize),
+    pub data: usize,
+}
+
+impl Work {
+    fn run(self) {
+        (self.function)(self.data)
+    }
+}
+
+static QUEUE: RingBuffer<Work, QUEUE_SIZE> = RingBuffer::new();
+/// Set while the queue is being drained, so that nested interrupts don't
+/// drain it again.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Call `function` with `data` once the current interrupt has been handled.
+///
+/// Can be called from any context and never blocks. Returns `false` and
+/// drops the work if too much work is pending.
+pub fn defer(function: fn(usize), data: usize) -> bool {
+    QUEUE.push(Work { function, data }).is_ok()
+}
+
+/// Returns the number of work items dropped because the queue was full.
+pub fn dropped() -> u64 {
+    QUEUE.overflows()
+}
+
+/// Run all pending work with interrupts enabled.
+///
+/// Called at the end of every IRQ, after the end of interrupt has been
+/// signalled. Returns with interrupts disabled.
+pub(super) fn run() {
+    loop {
+        if RUNNING.swap(true, Ordering::Acquire) {
+            // an outer interrupt is already draining the queue
+            return;
+        }
+
+        interrupts::enable();
+        while let Some(work) = QUEUE.pop() {
+            work.run();
+        }
+        interrupts::disable();
+        RUNNING.store(false, Ordering::Release);
+
+        // Work queued by an interrupt between the last pop and disabling
+        // interrupts would otherwise wait for the next interrupt.
+        if QUEUE.is_empty() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test_case]
+    fn test_work_runs_with_interrupts_enabled() {
+        static RAN: AtomicUsize = AtomicUsize::new(0);
+        static ENABLED: AtomicBool = AtomicBool::new(false);
+
+        fn work(data: usize) {
+            ENABLED.store(interrupts::are_enabled(), Ordering::SeqCst);
+            RAN.store(data, Ordering::SeqCst);
+        }
+
+        assert!(defer(work, 42));
+        while RAN.load(Ordering::SeqCst) == 0 {
+            x86_64::instructions::hlt();
+        }
+        assert_eq!(RAN.load(Ordering::SeqCst), 42);
+        assert!(ENABLED.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,302 @@
+use core::arch::asm;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use x86_64::{
+    registers::control::{Cr2, Cr3},
+    structures::idt::{
+        DescriptorTable, InterruptDescriptorTable, PageFaultErrorCode, SelectorErrorCode,
+    },
+};
+
+use super::trap::{self, TrapFrame};
+use crate::backtrace::Backtrace;
+use crate::gdt;
+use crate::io::serial::SERIAL1;
+use crate::log;
+
+/// Register handlers for all CPU exceptions.
+///
+/// Every exception enters through an entry stub from [`trap`], which saves
+/// all registers and calls [`handle`].
+///
+/// Control protection (#CP) and hypervisor injection (#HV) exceptions
+/// are not covered, since the IDT type has no entries for them.
+pub fn register(idt: &mut InterruptDescriptorTable) {
+    // SAFETY: The entry stubs are valid exception handlers, which save
+    // and restore all registers and return with `iretq`.
+    unsafe {
+        idt.divide_error.set_handler_addr(trap::entry_point(0));
+        idt.debug.set_handler_addr(trap::entry_point(1));
+        idt.breakpoint.set_handler_addr(trap::entry_point(3));
+        idt.overflow.set_handler_addr(trap::entry_point(4));
+        idt.bound_range_exceeded
+            .set_handler_addr(trap::entry_point(5));
+        idt.invalid_opcode.set_handler_addr(trap::entry_point(6));
+        idt.device_not_available
+            .set_handler_addr(trap::entry_point(7));
+        idt.invalid_tss.set_handler_addr(trap::entry_point(10));
+        idt.segment_not_present
+            .set_handler_addr(trap::entry_point(11));
+        idt.stack_segment_fault
+            .set_handler_addr(trap::entry_point(12));
+        idt.general_protection_fault
+            .set_handler_addr(trap::entry_point(13));
+        idt.x87_floating_point
+            .set_handler_addr(trap::entry_point(16));
+        idt.alignment_check.set_handler_addr(trap::entry_point(17));
+        idt.simd_floating_point
+            .set_handler_addr(trap::entry_point(19));
+        idt.virtualization.set_handler_addr(trap::entry_point(20));
+        idt.vmm_communication_exception
+            .set_handler_addr(trap::entry_point(29));
+        idt.security_exception
+            .set_handler_addr(trap::entry_point(30));
+
+        idt.double_fault
+            .set_handler_addr(trap::entry_point(8))
+            .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        idt.non_maskable_interrupt
+            .set_handler_addr(trap::entry_point(2))
+            .set_stack_index(gdt::NMI_IST_INDEX);
+        idt.machine_check
+            .set_handler_addr(trap::entry_point(18))
+            .set_stack_index(gdt::MACHINE_CHECK_IST_INDEX);
+        // A page fault caused by a kernel stack overflow can't push
+        // anything on the faulting stack, so it gets its own one. Nested
+        // page faults are escalated, see `PAGE_FAULT_DEPTH`.
+        idt.page_fault
+            .set_handler_addr(trap::entry_point(14))
+            .set_stack_index(gdt::PAGE_FAULT_IST_INDEX);
+    }
+}
+
+/// Number of page faults being handled.
+///
+/// Every page fault starts at the top of the page fault IST stack, so a
+/// page fault raised by the handler itself overwrites the frames of the
+/// outer one. It is escalated to a panic on the double fault stack
+/// instead. Page faults are always fatal, so the depth never decreases.
+static PAGE_FAULT_DEPTH: AtomicU8 = AtomicU8::new(0);
+
+/// Handle the exception described by `frame`.
+///
+/// Debug, NMI, breakpoint and overflow exceptions are reported and
+/// execution continues, all other exceptions are fatal. Double faults and
+/// page faults additionally include a backtrace of the interrupted code.
+pub(super) fn handle(frame: &mut TrapFrame) {
+    if frame.vector == 14 {
+        match PAGE_FAULT_DEPTH.fetch_add(1, Ordering::SeqCst) {
+            0 => {}
+            // SAFETY: Nothing else runs on the double fault stack, unless a
+            // double fault is handled, which never returns either.
+            1 => unsafe { on_double_fault_stack(nested_page_fault, frame) },
+            // the report of the nested page fault faulted again
+            _ => crate::hlt_loop(),
+        }
+    }
+
+    let report = ExceptionReport {
+        frame,
+        error_code: decode_error_code(frame.vector as u8, frame.error_code),
+    };
+
+    match frame.vector {
+        2 => report_nmi(&report),
+        1 | 3 | 4 => log!("{}", report),
+        8 | 14 => panic!("{}\n{}", report, Backtrace::from_trap_frame(frame)),
+        _ => panic!("{}", report),
+    }
+}
+
+/// Call `handler` with `frame` on the double fault stack.
+///
+/// # Safety
+///
+/// The double fault stack must not be in use.
+unsafe fn on_double_fault_stack(handler: extern "C" fn(&TrapFrame) -> !, frame: &TrapFrame) -> ! {
+    // SAFETY: The stack top is 16 byte aligned and the stack unused, as
+    // guaranteed by the caller. `frame` stays valid, since nothing runs on
+    // the page fault stack anymore.
+    unsafe {
+        asm!(
+            "mov rsp, {stack}",
+            "call {handler}",
+            stack = in(reg) gdt::double_fault_stack_top().as_u64(),
+            handler = in(reg) handler,
+            in("rdi") frame,
+            options(noreturn),
+        )
+    }
+}
+
+extern "C" fn nested_page_fault(frame: &TrapFrame) -> ! {
+    let report = ExceptionReport {
+        frame,
+        error_code: decode_error_code(14, frame.error_code),
+    };
+    panic!(
+        "{}\n(raised while handling another page fault)\n{}",
+        report,
+        Backtrace::from_trap_frame(frame)
+    );
+}
+
+/// Report an NMI on the serial port, if it isn't in use.
+///
+/// An NMI can interrupt code holding any lock, so waiting for one could
+/// deadlock. NMIs are counted with the other interrupts in any case, see
+/// [`interrupt_count`](super::irq::interrupt_count).
+fn report_nmi(report: &ExceptionReport) {
+    if let Some(mut serial) = SERIAL1.try_lock() {
+        // nothing can be done if writing fails
+        let _ = writeln!(serial, "{}", report);
+    }
+}
+
+/// Returns the mnemonic and the name of the exception with the given vector.
+pub fn describe(vector: u8) -> (&'static str, &'static str) {
+    match vector {
+        0 => ("#DE", "DIVIDE ERROR"),
+        1 => ("#DB", "DEBUG"),
+        2 => ("NMI", "NON-MASKABLE INTERRUPT"),
+        3 => ("#BP", "BREAKPOINT"),
+        4 => ("#OF", "OVERFLOW"),
+        5 => ("#BR", "BOUND RANGE EXCEEDED"),
+        6 => ("#UD", "INVALID OPCODE"),
+        7 => ("#NM", "DEVICE NOT AVAILABLE"),
+        8 => ("#DF", "DOUBLE FAULT"),
+        10 => ("#TS", "INVALID TSS"),
+        11 => ("#NP", "SEGMENT NOT PRESENT"),
+        12 => ("#SS", "STACK SEGMENT FAULT"),
+        13 => ("#GP", "GENERAL PROTECTION FAULT"),
+        14 => ("#PF", "PAGE FAULT"),
+        16 => ("#MF", "X87 FLOATING POINT"),
+        17 => ("#AC", "ALIGNMENT CHECK"),
+        18 => ("#MC", "MACHINE CHECK"),
+        19 => ("#XM", "SIMD FLOATING POINT"),
+        20 => ("#VE", "VIRTUALIZATION"),
+        21 => ("#CP", "CONTROL PROTECTION"),
+        28 => ("#HV", "HYPERVISOR INJECTION"),
+        29 => ("#VC", "VMM COMMUNICATION"),
+        30 => ("#SX", "SECURITY"),
+        _ => ("???", "RESERVED"),
+    }
+}
+
+/// Decoded error code pushed by the CPU.
+#[derive(Debug, Clone, Copy)]
+pub enum ErrorCode {
+    None,
+    Raw(u64),
+    Selector(SelectorErrorCode),
+    PageFault(PageFaultErrorCode),
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Raw(code) => write!(f, "{:#x}", code),
+            Self::Selector(code) if code.is_null() => write!(f, "0x0 (no selector)"),
+            Self::Selector(code) => {
+                let table = match code.descriptor_table() {
+                    DescriptorTable::Gdt => "GDT",
+                    DescriptorTable::Idt => "IDT",
+                    DescriptorTable::Ldt => "LDT",
+                };
+                write!(
+                    f,
+                    "{} index {}{}",
+                    table,
+                    code.index(),
+                    if code.external() { ", external" } else { "" }
+                )
+            }
+            Self::PageFault(code) => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Everything known about an exception, printed in a uniform format.
+pub struct ExceptionReport<'a> {
+    pub frame: &'a TrapFrame,
+    pub error_code: ErrorCode,
+}
+
+impl fmt::Display for ExceptionReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let vector = self.frame.vector as u8;
+        let (mnemonic, name) = describe(vector);
+        writeln!(f, "EXCEPTION: {} ({}, vector {})", name, mnemonic, vector)?;
+        if !matches!(self.error_code, ErrorCode::None) {
+            writeln!(f, "  error code: {}", self.error_code)?;
+        }
+        writeln!(f, "{}", self.frame)?;
+        if let ErrorCode::PageFault(_) = self.error_code {
+            writeln!(f, "  CR2: {:#018x}", Cr2::read().as_u64())?;
+        }
+        write!(f, "  CR3: {:#018x}", Cr3::read().0.start_address().as_u64())
+    }
+}
+
+/// Decode the error code pushed for the exception with the given vector.
+fn decode_error_code(vector: u8, error_code: u64) -> ErrorCode {
+    match vector {
+        10..=13 => selector(error_code),
+        14 => ErrorCode::PageFault(PageFaultErrorCode::from_bits_truncate(error_code)),
+        17 | 29 | 30 => ErrorCode::Raw(error_code),
+        // the error code of a double fault is always 0
+        _ => ErrorCode::None,
+    }
+}
+
+fn selector(error_code: u64) -> ErrorCode {
+    ErrorCode::Selector(SelectorErrorCode::new_truncate(error_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test_case]
+    fn test_selector_error_code() {
+        // GDT entry 2, not external
+        assert_eq!(selector(0b10_000).to_string(), "GDT index 2");
+        // IDT entry 13, external
+        assert_eq!(
+            selector(13 << 3 | 0b011).to_string(),
+            "IDT index 13, external"
+        );
+        assert_eq!(selector(0).to_string(), "0x0 (no selector)");
+    }
+
+    #[test_case]
+    fn test_describe() {
+        assert_eq!(describe(13), ("#GP", "GENERAL PROTECTION FAULT"));
+        assert_eq!(describe(15), ("???", "RESERVED"));
+    }
+
+    #[test_case]
+    fn test_nmi_with_serial_locked() {
+        let count = super::super::irq::interrupt_count(2);
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let _serial = SERIAL1.lock();
+            // SAFETY: The NMI handler returns to the interrupted code.
+            unsafe { core::arch::asm!("int 2") };
+        });
+        assert_eq!(super::super::irq::interrupt_count(2), count + 1);
+    }
+
+    #[test_case]
+    fn test_decode_error_code() {
+        assert!(matches!(decode_error_code(8, 0), ErrorCode::None));
+        assert!(matches!(decode_error_code(13, 0), ErrorCode::Selector(_)));
+        assert!(matches!(
+            decode_error_code(14, 0b10),
+            ErrorCode::PageFault(code) if code == PageFaultErrorCode::CAUSED_BY_WRITE
+        ));
+    }
+}
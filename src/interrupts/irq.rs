@@ -0,0 +1,206 @@
+//! Registration and dispatch of hardware interrupt handlers.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use spin::RwLock;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::PIC_1_OFFSET;
+
+/// Number of legacy ISA IRQ lines.
+pub const IRQ_LINES: u8 = 16;
+
+/// Identifies a registered handler, see [`unregister_irq`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrqHandle {
+    line: u8,
+    id: u64,
+}
+
+impl IrqHandle {
+    pub fn line(&self) -> u8 {
+        self.line
+    }
+}
+
+/// Errors returned when registering an IRQ handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    /// The line is not below [`IRQ_LINES`].
+    InvalidLine(u8),
+}
+
+struct Handler {
+    id: u64,
+    name: &'static str,
+    handler: Box<dyn Fn() + Send + Sync>,
+}
+
+static HANDLERS: [RwLock<Vec<Handler>>; IRQ_LINES as usize] =
+    [const { RwLock::new(Vec::new()) }; IRQ_LINES as usize];
+
+/// Number of interrupts received per vector.
+static COUNTS: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+
+/// Returns the vector IRQ `line` is delivered as.
+pub fn vector(line: u8) -> u8 {
+    PIC_1_OFFSET + line
+}
+
+/// Returns the IRQ line delivered as `vector`, if any.
+pub fn line(vector: u8) -> Option<u8> {
+    vector
+        .checked_sub(PIC_1_OFFSET)
+        .filter(|&line| line < IRQ_LINES)
+}
+
+/// Call `handler` whenever IRQ `line` is raised.
+///
+/// Several handlers can share a line, they are called in the order they
+/// were registered. The line is unmasked with the first handler. Handlers
+/// run with interrupts disabled and must not register or unregister
+/// handlers themselves, anything slow should be left to
+/// [`defer`](super::deferred::defer). The end of interrupt is signalled
+/// after all handlers ran.
+pub fn register_irq(
+    line: u8,
+    name: &'static str,
+    handler: impl Fn() + Send + Sync + 'static,
+) -> Result<IrqHandle, IrqError> {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    if line >= IRQ_LINES {
+        return Err(IrqError::InvalidLine(line));
+    }
+    let handler = Handler {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        name,
+        handler: Box::new(handler),
+    };
+    let handle = IrqHandle {
+        line,
+        id: handler.id,
+    };
+
+    without_interrupts(|| {
+        let mut handlers = HANDLERS[usize::from(line)].write();
+        handlers.push(handler);
+        if handlers.len() == 1 {
+            super::unmask_irq(line);
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Remove a handler registered with [`register_irq`].
+///
+/// The line is masked once its last handler is removed. Returns `false`
+/// if the handler was already removed.
+pub fn unregister_irq(handle: IrqHandle) -> bool {
+    let handler = without_interrupts(|| {
+        let mut handlers = HANDLERS[usize::from(handle.line)].write();
+        let index = handlers
+            .iter()
+            .position(|handler| handler.id == handle.id)?;
+        let handler = handlers.remove(index);
+        if handlers.is_empty() {
+            super::mask_irq(handle.line);
+        }
+        Some(handler)
+    });
+
+    // the handler is dropped outside of the critical section
+    handler.is_some()
+}
+
+/// Returns the names of the handlers registered for IRQ `line`.
+pub fn handlers(line: u8) -> Vec<&'static str> {
+    without_interrupts(|| {
+        HANDLERS[usize::from(line)]
+            .read()
+            .iter()
+            .map(|handler| handler.name)
+            .collect()
+    })
+}
+
+/// Returns the number of interrupts received with `vector` since boot.
+pub fn interrupt_count(vector: u8) -> u64 {
+    COUNTS[usize::from(vector)].load(Ordering::Relaxed)
+}
+
+/// Count an interrupt with `vector`.
+pub(super) fn count(vector: u8) {
+    COUNTS[usize::from(vector)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Run the handlers of IRQ `line` and signal the end of the interrupt.
+pub(super) fn dispatch(line: u8) {
+    for handler in HANDLERS[usize::from(line)].read().iter() {
+        (handler.handler)();
+    }
+    super::end_of_interrupt(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test_case]
+    fn test_line_vector_mapping() {
+        assert_eq!(vector(0), 32);
+        assert_eq!(line(33), Some(1));
+        assert_eq!(line(47), Some(15));
+        assert_eq!(line(48), None);
+        assert_eq!(line(14), None);
+    }
+
+    #[test_case]
+    fn test_invalid_line() {
+        assert_eq!(
+            register_irq(IRQ_LINES, "invalid", || {}),
+            Err(IrqError::InvalidLine(IRQ_LINES))
+        );
+    }
+
+    #[test_case]
+    fn test_shared_line() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handle = register_irq(0, "test", {
+            let calls = calls.clone();
+            move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .unwrap();
+        assert_eq!(handlers(0), ["timer", "test"]);
+
+        let ticks = crate::time::ticks();
+        while crate::time::ticks() < ticks + 3 {
+            x86_64::instructions::hlt();
+        }
+        assert!(unregister_irq(handle));
+        assert!(!unregister_irq(handle));
+        assert_eq!(handlers(0), ["timer"]);
+
+        let received = calls.load(Ordering::SeqCst);
+        assert!(received >= 2, "received {} interrupts", received);
+    }
+
+    #[test_case]
+    fn test_interrupts_are_counted() {
+        let vector = vector(0);
+        let count = interrupt_count(vector);
+        let ticks = crate::time::ticks();
+        while crate::time::ticks() < ticks + 2 {
+            x86_64::instructions::hlt();
+        }
+        assert!(interrupt_count(vector) >= count + 2);
+    }
+}
@@ -0,0 +1,188 @@
+use lazy_static::lazy_static;
+use pic8259::ChainedPics;
+use spin::Once;
+
+use x86_64::{
+    instructions::port::Port,
+    structures::idt::{InterruptDescriptorTable, InterruptStackFrame},
+};
+
+use crate::io::keyboard;
+use crate::vga_println;
+
+pub mod apic;
+pub mod deferred;
+pub mod exceptions;
+pub mod irq;
+pub mod trap;
+
+pub use irq::{register_irq, unregister_irq};
+
+lazy_static! {
+    static ref IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+
+        exceptions::register(&mut idt);
+
+        for line in 0..irq::IRQ_LINES {
+            let vector = irq::vector(line);
+            // SAFETY: The entry stubs save and restore all registers and
+            // return with `iretq`.
+            unsafe {
+                idt[usize::from(vector)].set_handler_addr(trap::entry_point(vector));
+            }
+        }
+        idt[usize::from(apic::SPURIOUS_VECTOR)].set_handler_fn(spurious_interrupt_handler);
+
+        idt
+    };
+}
+
+pub fn init_idt() {
+    IDT.load();
+}
+
+/// The interrupt controller delivering hardware interrupts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    /// The legacy 8259 PICs.
+    Pic,
+    /// The local and I/O APICs.
+    Apic,
+}
+
+static CONTROLLER: Once<Controller> = Once::new();
+
+const PIC_1_DATA_IO_PORT: u16 = 0x21;
+const PIC_2_DATA_IO_PORT: u16 = 0xa1;
+/// Line of the primary PIC the secondary one is connected to.
+const PIC_CASCADE_LINE: u8 = 2;
+
+/// Set up the interrupt controller and register the handlers of the timer
+/// and the keyboard.
+///
+/// The APIC is used if the CPU has one, with the 8259 PIC as a fallback.
+/// All IRQ lines start masked, they are unmasked by [`register_irq`].
+pub fn init_controller() {
+    // The PICs are remapped even if they end up unused, so that their
+    // spurious interrupts don't collide with the exception vectors.
+    unsafe { PICS.lock().initialize() };
+
+    let controller = match apic::init() {
+        Ok(()) => {
+            // mask all interrupts of both PICs
+            unsafe {
+                Port::<u8>::new(PIC_1_DATA_IO_PORT).write(0xff);
+                Port::<u8>::new(PIC_2_DATA_IO_PORT).write(0xff);
+            }
+            Controller::Apic
+        }
+        Err(err) => {
+            vga_println!("APIC unavailable ({:?}), using the 8259 PIC", err);
+            // mask everything but the cascade from the secondary PIC
+            unsafe {
+                Port::<u8>::new(PIC_1_DATA_IO_PORT).write(!(1 << PIC_CASCADE_LINE));
+                Port::<u8>::new(PIC_2_DATA_IO_PORT).write(0xff);
+            }
+            Controller::Pic
+        }
+    };
+    CONTROLLER.call_once(|| controller);
+
+    register_irq(InterruptIndex::Timer.line(), "timer", crate::time::tick).unwrap();
+    register_irq(
+        InterruptIndex::Keyboard.line(),
+        "keyboard",
+        keyboard_interrupt,
+    )
+    .unwrap();
+}
+
+/// Returns the active interrupt controller, if it was set up.
+pub fn controller() -> Option<Controller> {
+    CONTROLLER.get().copied()
+}
+
+/// Signal the end of IRQ `line` to the active controller.
+fn end_of_interrupt(line: u8) {
+    match controller() {
+        Some(Controller::Apic) => apic::end_of_interrupt(),
+        _ => unsafe { PICS.lock().notify_end_of_interrupt(irq::vector(line)) },
+    }
+}
+
+/// Let the active controller deliver IRQ `line`.
+fn unmask_irq(line: u8) {
+    match controller() {
+        Some(Controller::Apic) => apic::route_irq(line, irq::vector(line)),
+        _ => update_pic_mask(line, false),
+    }
+}
+
+/// Stop the active controller from delivering IRQ `line`.
+fn mask_irq(line: u8) {
+    match controller() {
+        Some(Controller::Apic) => apic::mask_irq(line),
+        _ => update_pic_mask(line, true),
+    }
+}
+
+fn update_pic_mask(line: u8, masked: bool) {
+    let (mut port, bit) = match line {
+        0..8 => (Port::<u8>::new(PIC_1_DATA_IO_PORT), line),
+        _ => (Port::<u8>::new(PIC_2_DATA_IO_PORT), line - 8),
+    };
+    // SAFETY: Only the mask of the given line changes.
+    unsafe {
+        let mask = port.read();
+        port.write(if masked {
+            mask | 1 << bit
+        } else {
+            mask & !(1 << bit)
+        });
+    }
+}
+
+const PS2_IO_PORT: u16 = 0x60;
+
+fn keyboard_interrupt() {
+    let mut port = Port::new(PS2_IO_PORT);
+    let scancode: u8 = unsafe { port.read() };
+    keyboard::push_scancode(scancode);
+    crate::task::keyboard::wake();
+}
+
+/// Spurious interrupts of the local APIC must not be acknowledged.
+extern "x86-interrupt" fn spurious_interrupt_handler(_: InterruptStackFrame) {}
+
+pub const PIC_1_OFFSET: u8 = 32;
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+pub static PICS: spin::Mutex<ChainedPics> =
+    spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+/// Vectors of the IRQs handled by the kernel itself.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum InterruptIndex {
+    Timer = PIC_1_OFFSET,
+    Keyboard,
+}
+
+impl InterruptIndex {
+    fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    fn line(self) -> u8 {
+        self.as_u8() - PIC_1_OFFSET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test_case]
+    fn test_breakpoint_exception() {
+        x86_64::instructions::interrupts::int3();
+    }
+}
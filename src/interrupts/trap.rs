@@ -0,0 +1,304 @@
+use core::arch::naked_asm;
+use core::fmt;
+
+use x86_64::VirtAddr;
+
+use super::{deferred, exceptions, irq};
+
+/// Register state saved by the exception and IRQ entry stubs.
+///
+/// The general purpose registers are pushed by [`trap_common`], `vector`
+/// and `error_code` by the per-vector stub (a zero is pushed as the error
+/// code for vectors which don't have one) and the rest by the CPU.
+/// All fields are written back when returning from the exception, so
+/// handlers can modify them.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TrapFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    pub vector: u64,
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+impl fmt::Display for TrapFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registers = [
+            ("RAX", self.rax),
+            ("RBX", self.rbx),
+            ("RCX", self.rcx),
+            ("RDX", self.rdx),
+            ("RSI", self.rsi),
+            ("RDI", self.rdi),
+            ("RBP", self.rbp),
+            ("RSP", self.rsp),
+            ("R8", self.r8),
+            ("R9", self.r9),
+            ("R10", self.r10),
+            ("R11", self.r11),
+            ("R12", self.r12),
+            ("R13", self.r13),
+            ("R14", self.r14),
+            ("R15", self.r15),
+            ("RIP", self.rip),
+            ("RFLAGS", self.rflags),
+        ];
+
+        for (i, (name, value)) in registers.iter().enumerate() {
+            write!(f, "  {:>6}: {:#018x}", name, value)?;
+            if i % 3 == 2 || i == registers.len() - 1 {
+                writeln!(f)?;
+            }
+        }
+        write!(
+            f,
+            "  {:>6}: {:#06x}  {:>6}: {:#06x}",
+            "CS", self.cs, "SS", self.ss
+        )
+    }
+}
+
+/// Generates an entry stub for an exception without an error code or for
+/// an IRQ.
+macro_rules! stub {
+    ($name:ident, $vector:literal) => {
+        #[unsafe(naked)]
+        extern "C" fn $name() {
+            naked_asm!(
+                "push 0",
+                "push {vector}",
+                "jmp {common}",
+                vector = const $vector,
+                common = sym trap_common,
+            )
+        }
+    };
+}
+
+/// Generates an entry stub for an exception for which the CPU pushes an
+/// error code.
+macro_rules! stub_with_error_code {
+    ($name:ident, $vector:literal) => {
+        #[unsafe(naked)]
+        extern "C" fn $name() {
+            naked_asm!(
+                "push {vector}",
+                "jmp {common}",
+                vector = const $vector,
+                common = sym trap_common,
+            )
+        }
+    };
+}
+
+stub!(divide_error, 0);
+stub!(debug, 1);
+stub!(non_maskable_interrupt, 2);
+stub!(breakpoint, 3);
+stub!(overflow, 4);
+stub!(bound_range_exceeded, 5);
+stub!(invalid_opcode, 6);
+stub!(device_not_available, 7);
+stub_with_error_code!(double_fault, 8);
+stub_with_error_code!(invalid_tss, 10);
+stub_with_error_code!(segment_not_present, 11);
+stub_with_error_code!(stack_segment_fault, 12);
+stub_with_error_code!(general_protection_fault, 13);
+stub_with_error_code!(page_fault, 14);
+stub!(x87_floating_point, 16);
+stub_with_error_code!(alignment_check, 17);
+stub!(machine_check, 18);
+stub!(simd_floating_point, 19);
+stub!(virtualization, 20);
+stub_with_error_code!(vmm_communication, 29);
+stub_with_error_code!(security, 30);
+
+stub!(irq_0, 32);
+stub!(irq_1, 33);
+stub!(irq_2, 34);
+stub!(irq_3, 35);
+stub!(irq_4, 36);
+stub!(irq_5, 37);
+stub!(irq_6, 38);
+stub!(irq_7, 39);
+stub!(irq_8, 40);
+stub!(irq_9, 41);
+stub!(irq_10, 42);
+stub!(irq_11, 43);
+stub!(irq_12, 44);
+stub!(irq_13, 45);
+stub!(irq_14, 46);
+stub!(irq_15, 47);
+
+/// Returns the address of the entry stub for the given exception or IRQ
+/// vector.
+pub fn entry_point(vector: u8) -> VirtAddr {
+    let stub: extern "C" fn() = match vector {
+        0 => divide_error,
+        1 => debug,
+        2 => non_maskable_interrupt,
+        3 => breakpoint,
+        4 => overflow,
+        5 => bound_range_exceeded,
+        6 => invalid_opcode,
+        7 => device_not_available,
+        8 => double_fault,
+        10 => invalid_tss,
+        11 => segment_not_present,
+        12 => stack_segment_fault,
+        13 => general_protection_fault,
+        14 => page_fault,
+        16 => x87_floating_point,
+        17 => alignment_check,
+        18 => machine_check,
+        19 => simd_floating_point,
+        20 => virtualization,
+        29 => vmm_communication,
+        30 => security,
+        32 => irq_0,
+        33 => irq_1,
+        34 => irq_2,
+        35 => irq_3,
+        36 => irq_4,
+        37 => irq_5,
+        38 => irq_6,
+        39 => irq_7,
+        40 => irq_8,
+        41 => irq_9,
+        42 => irq_10,
+        43 => irq_11,
+        44 => irq_12,
+        45 => irq_13,
+        46 => irq_14,
+        47 => irq_15,
+        _ => panic!("no entry stub for vector {}", vector),
+    };
+
+    VirtAddr::new(stub as usize as u64)
+}
+
+/// Common part of all entry stubs.
+///
+/// Saves the general purpose registers, so that together with what was
+/// pushed before they form a [`TrapFrame`], and passes it to
+/// [`trap_dispatch`]. Afterwards all registers are restored from the
+/// (possibly modified) frame.
+///
+/// The CPU aligns the stack to 16 bytes before pushing the interrupt frame.
+/// Together with the error code, the vector and the 15 registers an even
+/// number of quadwords is pushed, so the stack is still aligned for the call.
+#[unsafe(naked)]
+extern "C" fn trap_common() {
+    naked_asm!(
+        "push rax",
+        "push rbx",
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push rbp",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov rdi, rsp",
+        "cld",
+        "call {dispatch}",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rbp",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop rcx",
+        "pop rbx",
+        "pop rax",
+        // skip the vector and the error code
+        "add rsp, 16",
+        "iretq",
+        dispatch = sym trap_dispatch,
+    )
+}
+
+extern "C" fn trap_dispatch(frame: &mut TrapFrame) {
+    let vector = frame.vector as u8;
+    irq::count(vector);
+    match irq::line(vector) {
+        Some(line) => {
+            irq::dispatch(line);
+            deferred::run();
+        }
+        None => exceptions::handle(frame),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::arch::asm;
+    use core::mem;
+
+    use super::*;
+
+    #[test_case]
+    fn test_trap_frame_layout() {
+        assert_eq!(mem::size_of::<TrapFrame>(), 22 * 8);
+        assert_eq!(mem::offset_of!(TrapFrame, vector), 15 * 8);
+        assert_eq!(mem::offset_of!(TrapFrame, rip), 17 * 8);
+    }
+
+    #[test_case]
+    fn test_registers_survive_exception() {
+        let (rbx, r12, r13, r14, r15): (u64, u64, u64, u64, u64);
+        unsafe {
+            asm!(
+                "push rbx",
+                "mov rbx, 0x1111",
+                "mov r12, 0x2222",
+                "mov r13, 0x3333",
+                "mov r14, 0x4444",
+                "mov r15, 0x5555",
+                "int3",
+                "mov {rbx}, rbx",
+                "pop rbx",
+                rbx = out(reg) rbx,
+                out("r12") r12,
+                out("r13") r13,
+                out("r14") r14,
+                out("r15") r15,
+            );
+        }
+
+        assert_eq!(
+            (rbx, r12, r13, r14, r15),
+            (0x1111, 0x2222, 0x3333, 0x4444, 0x5555)
+        );
+    }
+}
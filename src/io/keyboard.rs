@@ -0,0 +1,83 @@
+//! Scancodes received from the PS/2 keyboard.
+//!
+//! The keyboard interrupt handler only pushes the raw scancodes into a
+//! queue, from which they are decoded by whoever consumes them.
+
+use lazy_static::lazy_static;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use spin::Mutex;
+
+use crate::ring_buffer::RingBuffer;
+
+/// Number of scancodes buffered before new ones are dropped.
+pub const SCANCODE_QUEUE_SIZE: usize = 128;
+
+static SCANCODES: RingBuffer<u8, SCANCODE_QUEUE_SIZE> = RingBuffer::new();
+
+lazy_static! {
+    static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> = Mutex::new(
+        Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore)
+    );
+}
+
+/// Queue a scancode read by the interrupt handler.
+///
+/// Never blocks. If the queue is full the scancode is dropped, which is
+/// counted in [`overflows`].
+pub(crate) fn push_scancode(scancode: u8) {
+    let _ = SCANCODES.push(scancode);
+}
+
+/// Remove the oldest scancode from the queue.
+///
+/// Each scancode is returned only once, so there should be a single
+/// consumer at a time.
+pub fn pop_scancode() -> Option<u8> {
+    SCANCODES.pop()
+}
+
+/// Returns an iterator removing all queued scancodes.
+pub fn scancodes() -> impl Iterator<Item = u8> {
+    SCANCODES.drain()
+}
+
+/// Returns the number of scancodes dropped because nobody consumed them.
+pub fn overflows() -> u64 {
+    SCANCODES.overflows()
+}
+
+/// Feed a scancode to the US keyboard decoder, returning the key once a
+/// complete key press was received.
+///
+/// Must not be called from interrupt handlers.
+pub fn decode(scancode: u8) -> Option<DecodedKey> {
+    let mut keyboard = KEYBOARD.lock();
+    let key_event = keyboard.add_byte(scancode).ok()??;
+    keyboard.process_keyevent(key_event)
+}
+
+#[cfg(test)]
+mod tests {
+    use x86_64::instructions::interrupts::without_interrupts;
+
+    use super::*;
+
+    #[test_case]
+    fn test_scancode_queue() {
+        without_interrupts(|| {
+            scancodes().for_each(drop);
+            push_scancode(0x1e);
+            push_scancode(0x9e);
+            assert_eq!(pop_scancode(), Some(0x1e));
+            assert!(scancodes().eq([0x9e]));
+            assert_eq!(pop_scancode(), None);
+        });
+    }
+
+    #[test_case]
+    fn test_decode() {
+        // press and release of A
+        assert_eq!(decode(0x1e), Some(DecodedKey::Unicode('a')));
+        assert_eq!(decode(0x9e), None);
+    }
+}
@@ -0,0 +1,24 @@
+use core::fmt;
+
+use crate::time::SystemTime;
+
+/// Prints a timestamped line to the screen and the serial interface.
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => ($crate::io::log::_log(format_args!($($arg)*)));
+}
+
+#[doc(hidden)]
+pub fn _log(args: fmt::Arguments) {
+    let now = SystemTime::now();
+    crate::vga_println!("[{}] {}", now, args);
+    crate::serial_println!("[{}] {}", now, args);
+}
+
+#[cfg(test)]
+mod tests {
+    #[test_case]
+    fn test_log() {
+        log!("test_log output {}", 42);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod keyboard;
+pub mod log;
 pub mod serial;
 pub mod vga;
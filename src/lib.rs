@@ -4,21 +4,46 @@
 #![test_runner(crate::testing::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 #![feature(abi_x86_interrupt)]
+#![feature(alloc_error_handler)]
 
+extern crate alloc;
+
+use bootloader::BootInfo;
+
+pub mod acpi;
+pub mod allocator;
+pub mod backtrace;
 pub mod echo;
 pub mod gdt;
 pub mod interrupts;
 pub mod io;
 pub mod mem;
+pub mod power;
+pub mod ring_buffer;
+pub mod symbols;
+pub mod task;
 pub mod testing;
+pub mod time;
 
-pub fn init() {
+pub fn init(boot_info: &'static BootInfo) {
+    init_memory(boot_info);
+    if let Err(err) = acpi::init() {
+        log!("ACPI tables not found: {:?}", err);
+    }
     gdt::init();
     interrupts::init_idt();
-    unsafe { interrupts::PICS.lock().initialize() };
+    interrupts::init_controller();
+    time::init(time::TimerSource::detect(), time::TIMER_FREQUENCY);
     x86_64::instructions::interrupts::enable();
 }
 
+/// Set up physical and virtual memory management and the kernel heap.
+pub fn init_memory(boot_info: &'static BootInfo) {
+    mem::init(boot_info);
+    allocator::init_heap().expect("heap initialization failed");
+    mem::vma::init(&boot_info.memory_map);
+}
+
 pub fn hlt_loop() -> ! {
     loop {
         x86_64::instructions::hlt();
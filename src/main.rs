@@ -9,61 +9,37 @@ use core::panic::PanicInfo;
 
 use bootloader::{entry_point, BootInfo};
 
-use os::mem;
-use os::vga_println;
+use os::backtrace::{self, Backtrace};
+use os::task::{Executor, Task};
+use os::{serial_println, vga_println};
 
 #[panic_handler]
 fn handler(info: &PanicInfo) -> ! {
     vga_println!("{}", info);
+    serial_println!("{}", info);
+    backtrace::print(&Backtrace::capture());
 
     os::hlt_loop()
 }
 
-#[allow(dead_code)]
-fn test(boot_info: &'static BootInfo) {
-    use x86_64::{structures::paging::Translate, VirtAddr};
-
-    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
-    let mapper = unsafe { mem::init_mapper(phys_mem_offset) };
-
-    let addresses = [
-        // the identity-mapped vga buffer page
-        0xb8000,
-        // some code page
-        0x201008,
-        // some stack page
-        0x0100_0020_1a10,
-        // virtual address mapped to physical address 0
-        boot_info.physical_memory_offset,
-    ];
-
-    for &address in &addresses {
-        let virt = VirtAddr::new(address);
-        let phys = mapper.translate_addr(virt);
-        vga_println!("{:?} -> {:?}", virt, phys);
-    }
-
-    os::hlt_loop();
-}
-
 #[cfg(not(test))]
 entry_point!(kernel_main);
 #[cfg(test)]
 entry_point!(test_kernel_main);
 
 pub fn kernel_main(boot_info: &'static BootInfo) -> ! {
-    os::init();
-
-    test(boot_info);
+    os::init(boot_info);
 
     os::echo::init();
 
-    os::hlt_loop()
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(os::echo::run()));
+    executor.run()
 }
 
 #[cfg(test)]
-fn test_kernel_main(_: &'static BootInfo) -> ! {
-    os::init();
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
+    os::init(boot_info);
 
     test_main();
 
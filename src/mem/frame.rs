@@ -0,0 +1,262 @@
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use spin::Mutex;
+use x86_64::{
+    structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+const FRAME_SIZE: u64 = 4096;
+const BITS_PER_WORD: usize = u64::BITS as usize;
+
+/// Snapshot of the physical frame allocator usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    pub total: usize,
+    pub used: usize,
+    pub free: usize,
+}
+
+/// Physical frame allocator that keeps one bit per frame.
+///
+/// A set bit means that the frame is either in use or not usable at all.
+/// The bitmap itself lives in the first usable region that is big enough
+/// to hold it, and the frames it occupies are marked as used.
+pub struct BitmapFrameAllocator {
+    memory_map: &'static MemoryMap,
+    bitmap: &'static mut [u64],
+    total_frames: usize,
+    used_frames: usize,
+    next_free: usize,
+}
+
+impl BitmapFrameAllocator {
+    /// Create a new frame allocator from the passed memory map.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that the passed memory map is valid, that
+    /// the complete physical memory is mapped at `physical_memory_offset`
+    /// and that this function is only called once, since the bitmap is
+    /// placed in one of the usable regions.
+    pub unsafe fn new(memory_map: &'static MemoryMap, physical_memory_offset: VirtAddr) -> Self {
+        let usable_regions = || {
+            memory_map
+                .iter()
+                .filter(|region| region.region_type == MemoryRegionType::Usable)
+        };
+
+        let frame_count = usable_regions()
+            .map(|region| region.range.end_frame_number)
+            .max()
+            .unwrap_or(0) as usize;
+        let words = frame_count.div_ceil(BITS_PER_WORD);
+        let bitmap_bytes = (words * core::mem::size_of::<u64>()) as u64;
+        let bitmap_frames = bitmap_bytes.div_ceil(FRAME_SIZE);
+
+        let bitmap_region = usable_regions()
+            .find(|region| {
+                region.range.end_frame_number - region.range.start_frame_number >= bitmap_frames
+            })
+            .expect("no usable region can hold the frame bitmap");
+        let bitmap_start = bitmap_region.range.start_addr();
+
+        let bitmap_ptr: *mut u64 = (physical_memory_offset + bitmap_start).as_mut_ptr();
+        // SAFETY: The region is usable, big enough and mapped at the passed
+        // offset. Nobody else has a reference to it, as guaranteed by the caller.
+        let bitmap = unsafe { core::slice::from_raw_parts_mut(bitmap_ptr, words) };
+        bitmap.fill(u64::MAX);
+
+        let mut allocator = Self {
+            memory_map,
+            bitmap,
+            total_frames: 0,
+            used_frames: 0,
+            next_free: 0,
+        };
+
+        for region in usable_regions() {
+            let start = region.range.start_frame_number as usize;
+            let end = region.range.end_frame_number as usize;
+            for frame in start..end {
+                allocator.clear_bit(frame);
+            }
+            allocator.total_frames += end - start;
+        }
+
+        let first_bitmap_frame = (bitmap_start / FRAME_SIZE) as usize;
+        for frame in first_bitmap_frame..first_bitmap_frame + bitmap_frames as usize {
+            allocator.set_bit(frame);
+            allocator.used_frames += 1;
+        }
+
+        allocator
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            total: self.total_frames,
+            used: self.used_frames,
+            free: self.total_frames - self.used_frames,
+        }
+    }
+
+    /// Returns whether `frame` lies in a usable region of the memory map.
+    fn is_usable(&self, frame: usize) -> bool {
+        let frame = frame as u64;
+        self.memory_map.iter().any(|region| {
+            region.region_type == MemoryRegionType::Usable
+                && (region.range.start_frame_number..region.range.end_frame_number).contains(&frame)
+        })
+    }
+
+    fn is_set(&self, frame: usize) -> bool {
+        self.bitmap[frame / BITS_PER_WORD] & (1 << (frame % BITS_PER_WORD)) != 0
+    }
+
+    fn set_bit(&mut self, frame: usize) {
+        self.bitmap[frame / BITS_PER_WORD] |= 1 << (frame % BITS_PER_WORD);
+    }
+
+    fn clear_bit(&mut self, frame: usize) {
+        self.bitmap[frame / BITS_PER_WORD] &= !(1 << (frame % BITS_PER_WORD));
+    }
+
+    /// Find the first clear bit, starting the search at `next_free`.
+    fn find_free(&self) -> Option<usize> {
+        let words = self.bitmap.len();
+        let first_word = self.next_free / BITS_PER_WORD;
+
+        (first_word..words)
+            .chain(0..first_word)
+            .find(|&word| self.bitmap[word] != u64::MAX)
+            .map(|word| word * BITS_PER_WORD + self.bitmap[word].trailing_ones() as usize)
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        let frame = self.find_free()?;
+        self.set_bit(frame);
+        self.used_frames += 1;
+        self.next_free = frame + 1;
+
+        Some(PhysFrame::containing_address(PhysAddr::new(
+            frame as u64 * FRAME_SIZE,
+        )))
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BitmapFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        let frame = (frame.start_address().as_u64() / FRAME_SIZE) as usize;
+        // Frames outside of usable regions have their bit set as well, but
+        // must never be handed out.
+        assert!(
+            frame < self.bitmap.len() * BITS_PER_WORD && self.is_usable(frame),
+            "deallocation of physical frame {} outside of usable memory",
+            frame
+        );
+        assert!(
+            self.is_set(frame),
+            "double free of physical frame {}",
+            frame
+        );
+
+        self.clear_bit(frame);
+        self.used_frames -= 1;
+        self.next_free = self.next_free.min(frame);
+    }
+}
+
+static FRAME_ALLOCATOR: Mutex<Option<BitmapFrameAllocator>> = Mutex::new(None);
+
+/// Initialize the global frame allocator.
+///
+/// # Safety
+///
+/// Same as for [`BitmapFrameAllocator::new`].
+pub unsafe fn init(memory_map: &'static MemoryMap, physical_memory_offset: VirtAddr) {
+    let allocator = unsafe { BitmapFrameAllocator::new(memory_map, physical_memory_offset) };
+    *FRAME_ALLOCATOR.lock() = Some(allocator);
+}
+
+/// Returns usage statistics of the global frame allocator.
+pub fn stats() -> FrameStats {
+    FRAME_ALLOCATOR
+        .lock()
+        .as_ref()
+        .expect("frame allocator is not initialized")
+        .stats()
+}
+
+/// Handle to the global frame allocator.
+///
+/// It can be passed wherever a `FrameAllocator` is needed, e.g. to
+/// `Mapper::map_to`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalFrameAllocator;
+
+unsafe impl FrameAllocator<Size4KiB> for GlobalFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        FRAME_ALLOCATOR.lock().as_mut()?.allocate_frame()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for GlobalFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        let mut allocator = FRAME_ALLOCATOR.lock();
+        let allocator = allocator
+            .as_mut()
+            .expect("frame allocator is not initialized");
+        unsafe { allocator.deallocate_frame(frame) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_allocate_updates_stats() {
+        let before = stats();
+        let frame = GlobalFrameAllocator
+            .allocate_frame()
+            .expect("out of frames");
+        let during = stats();
+        unsafe { GlobalFrameAllocator.deallocate_frame(frame) };
+
+        assert_eq!(during.used, before.used + 1);
+        assert_eq!(during.free, before.free - 1);
+        assert_eq!(stats(), before);
+    }
+
+    #[test_case]
+    fn test_deallocated_frame_is_reused() {
+        let frame = GlobalFrameAllocator
+            .allocate_frame()
+            .expect("out of frames");
+        unsafe { GlobalFrameAllocator.deallocate_frame(frame) };
+        let again = GlobalFrameAllocator
+            .allocate_frame()
+            .expect("out of frames");
+        unsafe { GlobalFrameAllocator.deallocate_frame(again) };
+
+        assert_eq!(frame, again);
+    }
+
+    #[test_case]
+    fn test_frames_are_distinct() {
+        let a = GlobalFrameAllocator
+            .allocate_frame()
+            .expect("out of frames");
+        let b = GlobalFrameAllocator
+            .allocate_frame()
+            .expect("out of frames");
+        unsafe {
+            GlobalFrameAllocator.deallocate_frame(a);
+            GlobalFrameAllocator.deallocate_frame(b);
+        }
+
+        assert_ne!(a, b);
+    }
+}
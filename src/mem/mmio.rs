@@ -0,0 +1,129 @@
+use x86_64::{
+    structures::paging::{PageTableFlags, PhysFrame},
+    PhysAddr, VirtAddr,
+};
+
+use super::paging::{self, PagingError};
+use super::vma::{self, Region, RegionKind, VmaError};
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Errors returned when mapping device memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmioError {
+    Vma(VmaError),
+    Paging(PagingError),
+}
+
+/// Uncached mapping of the registers of a memory mapped device.
+#[derive(Debug)]
+pub struct MmioRegion {
+    region: Region,
+    phys: PhysAddr,
+    size: u64,
+}
+
+impl MmioRegion {
+    /// Returns the virtual address of the first register.
+    pub fn base(&self) -> VirtAddr {
+        self.region.start + self.phys.as_u64() % PAGE_SIZE
+    }
+
+    /// Returns the physical address of the first register.
+    pub fn phys(&self) -> PhysAddr {
+        self.phys
+    }
+
+    pub fn read_u32(&self, offset: u64) -> u32 {
+        // SAFETY: The register lies within the mapping.
+        unsafe { self.register::<u32>(offset).read_volatile() }
+    }
+
+    pub fn write_u32(&self, offset: u64, value: u32) {
+        // SAFETY: The register lies within the mapping.
+        unsafe { self.register::<u32>(offset).write_volatile(value) }
+    }
+
+    pub fn read_u64(&self, offset: u64) -> u64 {
+        // SAFETY: The register lies within the mapping.
+        unsafe { self.register::<u64>(offset).read_volatile() }
+    }
+
+    pub fn write_u64(&self, offset: u64, value: u64) {
+        // SAFETY: The register lies within the mapping.
+        unsafe { self.register::<u64>(offset).write_volatile(value) }
+    }
+
+    fn register<T>(&self, offset: u64) -> *mut T {
+        let size = core::mem::size_of::<T>() as u64;
+        assert!(
+            offset + size <= self.size && offset.is_multiple_of(size),
+            "invalid register offset {:#x}",
+            offset
+        );
+        (self.base() + offset).as_mut_ptr()
+    }
+}
+
+/// Map `size` bytes of device memory starting at `phys`.
+///
+/// # Safety
+///
+/// The caller must guarantee that the physical range belongs to a device
+/// and not to RAM, which could be handed out by the frame allocator.
+pub unsafe fn map(phys: PhysAddr, size: u64) -> Result<MmioRegion, MmioError> {
+    let start = phys.align_down(PAGE_SIZE);
+    let mapped_size = (phys - start + size).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+
+    let region = vma::allocate(mapped_size, RegionKind::Mmio).map_err(MmioError::Vma)?;
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH
+        | PageTableFlags::NO_EXECUTE;
+    let frame = PhysFrame::containing_address(start);
+    // SAFETY: Guaranteed by the caller.
+    let result = paging::with_kernel_space(|space| unsafe {
+        space.map_physical(region.pages(), frame, flags)
+    });
+    if let Err(err) = result {
+        vma::release(region.start).expect("failed to release an MMIO region");
+        return Err(MmioError::Paging(err));
+    }
+
+    Ok(MmioRegion { region, phys, size })
+}
+
+/// Unmap the device memory and release its virtual memory.
+pub fn unmap(mmio: MmioRegion) -> Result<(), MmioError> {
+    paging::with_kernel_space(|space| space.unmap_physical(mmio.region.pages()))
+        .map_err(MmioError::Paging)?;
+    vma::release(mmio.region.start).map_err(MmioError::Vma)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the VGA text buffer is a convenient device to test with
+    const VGA_BUFFER: u64 = 0xb8000;
+
+    #[test_case]
+    fn test_map_vga_buffer() {
+        let mmio = unsafe { map(PhysAddr::new(VGA_BUFFER + 8), 16) }.expect("mapping failed");
+        assert_eq!(mmio.base().as_u64() % PAGE_SIZE, 8);
+        assert_eq!(
+            paging::with_kernel_space(|space| space.translate(mmio.base())),
+            Ok(PhysAddr::new(VGA_BUFFER + 8))
+        );
+
+        let identity = (VGA_BUFFER + 8) as *const u32;
+        assert_eq!(mmio.read_u32(0), unsafe { identity.read_volatile() });
+
+        let start = mmio.region.start;
+        unmap(mmio).expect("unmapping failed");
+        assert_eq!(vma::find(start), None);
+    }
+}
@@ -1,9 +1,47 @@
+use bootloader::BootInfo;
+use spin::Once;
 use x86_64::{
     registers::control::Cr3,
     structures::paging::{OffsetPageTable, PageTable},
-    VirtAddr,
+    PhysAddr, VirtAddr,
 };
 
+pub mod frame;
+pub mod mmio;
+pub mod paging;
+pub mod stack;
+pub mod vma;
+
+static PHYSICAL_MEMORY_OFFSET: Once<VirtAddr> = Once::new();
+
+/// Initialize memory management from the information passed by the bootloader.
+///
+/// This sets up the global physical frame allocator and the kernel
+/// address space. It must be called exactly once, before anything else
+/// touches physical memory.
+pub fn init(boot_info: &'static BootInfo) {
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    PHYSICAL_MEMORY_OFFSET.call_once(|| physical_memory_offset);
+
+    // SAFETY: The bootloader maps the complete physical memory at the
+    // passed offset (`map_physical_memory` feature) and the memory map
+    // comes straight from it.
+    unsafe { frame::init(&boot_info.memory_map, physical_memory_offset) };
+
+    // SAFETY: Same as above, and this is the only place creating the mapper.
+    let mapper = unsafe { init_mapper(physical_memory_offset) };
+    paging::init(mapper);
+}
+
+/// Returns the virtual address under which the passed physical address is
+/// accessible.
+pub fn phys_to_virt(addr: PhysAddr) -> VirtAddr {
+    let offset = PHYSICAL_MEMORY_OFFSET
+        .get()
+        .expect("memory management is not initialized");
+    *offset + addr.as_u64()
+}
+
 /// Initialize a new OffsetPageTable.
 ///
 /// This function is unsafe because the caller must guarantee that the
@@ -0,0 +1,392 @@
+use spin::Mutex;
+use x86_64::{
+    instructions::interrupts::without_interrupts,
+    structures::paging::{
+        mapper::{FlagUpdateError, MapToError, TranslateError, TranslateResult, UnmapError},
+        FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageTableFlags, PhysFrame,
+        Size4KiB, Translate,
+    },
+    PhysAddr, VirtAddr,
+};
+
+use super::frame::GlobalFrameAllocator;
+
+pub type PageRange = x86_64::structures::paging::page::PageRange<Size4KiB>;
+
+/// Errors returned by the [`AddressSpace`] operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagingError {
+    /// There are no physical frames left.
+    FrameAllocationFailed,
+    /// The page is already mapped.
+    PageAlreadyMapped(Page),
+    /// The page is not mapped.
+    PageNotMapped(Page),
+    /// The page is part of a huge page, which this API does not manage.
+    ParentEntryHugePage(Page),
+    /// The page table entry points to an invalid physical address.
+    InvalidFrameAddress(PhysAddr),
+}
+
+impl PagingError {
+    fn from_map_error(page: Page, err: MapToError<Size4KiB>) -> Self {
+        match err {
+            MapToError::FrameAllocationFailed => Self::FrameAllocationFailed,
+            MapToError::ParentEntryHugePage => Self::ParentEntryHugePage(page),
+            MapToError::PageAlreadyMapped(_) => Self::PageAlreadyMapped(page),
+        }
+    }
+
+    fn from_unmap_error(page: Page, err: UnmapError) -> Self {
+        match err {
+            UnmapError::ParentEntryHugePage => Self::ParentEntryHugePage(page),
+            UnmapError::PageNotMapped => Self::PageNotMapped(page),
+            UnmapError::InvalidFrameAddress(addr) => Self::InvalidFrameAddress(addr),
+        }
+    }
+
+    fn from_translate_error(page: Page, err: TranslateError) -> Self {
+        match err {
+            TranslateError::PageNotMapped => Self::PageNotMapped(page),
+            TranslateError::ParentEntryHugePage => Self::ParentEntryHugePage(page),
+            TranslateError::InvalidFrameAddress(addr) => Self::InvalidFrameAddress(addr),
+        }
+    }
+
+    fn from_flag_update_error(page: Page, err: FlagUpdateError) -> Self {
+        match err {
+            FlagUpdateError::PageNotMapped => Self::PageNotMapped(page),
+            FlagUpdateError::ParentEntryHugePage => Self::ParentEntryHugePage(page),
+        }
+    }
+}
+
+/// The set of page tables describing a virtual address space.
+///
+/// All operations either succeed for the whole range, or leave the
+/// address space unchanged.
+pub struct AddressSpace {
+    mapper: OffsetPageTable<'static>,
+}
+
+impl AddressSpace {
+    pub fn new(mapper: OffsetPageTable<'static>) -> Self {
+        Self { mapper }
+    }
+
+    /// Map every page in `pages` to a freshly allocated frame.
+    pub fn map_range(
+        &mut self,
+        pages: PageRange,
+        flags: PageTableFlags,
+    ) -> Result<(), PagingError> {
+        let mut frame_allocator = GlobalFrameAllocator;
+
+        for page in pages {
+            let result = frame_allocator
+                .allocate_frame()
+                .ok_or(PagingError::FrameAllocationFailed)
+                .and_then(|frame| {
+                    let result = self.map_page(page, frame, flags);
+                    if result.is_err() {
+                        unsafe { frame_allocator.deallocate_frame(frame) };
+                    }
+                    result
+                });
+
+            if let Err(err) = result {
+                self.unmap_range(PageRange {
+                    start: pages.start,
+                    end: page,
+                })
+                .expect("failed to roll back a partial mapping");
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unmap every page in `pages` and free the frames backing them.
+    pub fn unmap_range(&mut self, pages: PageRange) -> Result<(), PagingError> {
+        self.check_mapped(pages)?;
+
+        let mut frame_allocator = GlobalFrameAllocator;
+        for page in pages {
+            let (frame, flush) = self
+                .mapper
+                .unmap(page)
+                .map_err(|err| PagingError::from_unmap_error(page, err))?;
+            flush.flush();
+            unsafe { frame_allocator.deallocate_frame(frame) };
+        }
+
+        Ok(())
+    }
+
+    /// Map `pages` to the contiguous physical memory starting at `start`.
+    ///
+    /// Unlike [`map_range`](Self::map_range) no frames are allocated, which
+    /// makes this suitable for memory mapped devices.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that the physical memory is not handed out
+    /// by the frame allocator, e.g. because it doesn't belong to RAM.
+    pub unsafe fn map_physical(
+        &mut self,
+        pages: PageRange,
+        start: PhysFrame,
+        flags: PageTableFlags,
+    ) -> Result<(), PagingError> {
+        for (i, page) in pages.enumerate() {
+            if let Err(err) = self.map_page(page, start + i as u64, flags) {
+                self.unmap_physical(PageRange {
+                    start: pages.start,
+                    end: page,
+                })
+                .expect("failed to roll back a partial mapping");
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unmap every page in `pages` without freeing the frames backing them.
+    ///
+    /// This is the counterpart to [`map_physical`](Self::map_physical).
+    pub fn unmap_physical(&mut self, pages: PageRange) -> Result<(), PagingError> {
+        self.check_mapped(pages)?;
+
+        for page in pages {
+            let (_, flush) = self
+                .mapper
+                .unmap(page)
+                .map_err(|err| PagingError::from_unmap_error(page, err))?;
+            flush.flush();
+        }
+
+        Ok(())
+    }
+
+    /// Change the flags of every page in `pages`.
+    pub fn protect(&mut self, pages: PageRange, flags: PageTableFlags) -> Result<(), PagingError> {
+        self.check_mapped(pages)?;
+
+        for page in pages {
+            // SAFETY: The mappings stay the same, only their flags change.
+            // It's up to the caller to not remove access still relied upon.
+            unsafe { self.mapper.update_flags(page, flags) }
+                .map_err(|err| PagingError::from_flag_update_error(page, err))?
+                .flush();
+        }
+
+        Ok(())
+    }
+
+    /// Translate a virtual address to the physical address it is mapped to.
+    pub fn translate(&self, addr: VirtAddr) -> Result<PhysAddr, PagingError> {
+        match self.mapper.translate(addr) {
+            TranslateResult::Mapped { frame, offset, .. } => Ok(frame.start_address() + offset),
+            TranslateResult::NotMapped => {
+                Err(PagingError::PageNotMapped(Page::containing_address(addr)))
+            }
+            TranslateResult::InvalidFrameAddress(addr) => {
+                Err(PagingError::InvalidFrameAddress(addr))
+            }
+        }
+    }
+
+    /// Returns the flags of the mapping containing `addr`.
+    pub fn flags(&self, addr: VirtAddr) -> Result<PageTableFlags, PagingError> {
+        match self.mapper.translate(addr) {
+            TranslateResult::Mapped { flags, .. } => Ok(flags),
+            TranslateResult::NotMapped => {
+                Err(PagingError::PageNotMapped(Page::containing_address(addr)))
+            }
+            TranslateResult::InvalidFrameAddress(addr) => {
+                Err(PagingError::InvalidFrameAddress(addr))
+            }
+        }
+    }
+
+    /// Returns which of the level 4 page table entries are in use.
+    pub fn used_level_4_entries(&mut self) -> [bool; 512] {
+        let mut used = [false; 512];
+        for (used, entry) in used.iter_mut().zip(self.mapper.level_4_table().iter()) {
+            *used = !entry.is_unused();
+        }
+        used
+    }
+
+    /// Make sure that every page in `pages` is mapped with a 4KiB page.
+    fn check_mapped(&self, pages: PageRange) -> Result<(), PagingError> {
+        for page in pages {
+            self.mapper
+                .translate_page(page)
+                .map_err(|err| PagingError::from_translate_error(page, err))?;
+        }
+
+        Ok(())
+    }
+
+    fn map_page(
+        &mut self,
+        page: Page,
+        frame: PhysFrame,
+        flags: PageTableFlags,
+    ) -> Result<(), PagingError> {
+        // SAFETY: The frame either comes straight from the frame allocator,
+        // so nothing else refers to it, or the caller of `map_physical`
+        // guaranteed that it is not RAM.
+        unsafe {
+            self.mapper
+                .map_to(page, frame, flags, &mut GlobalFrameAllocator)
+                .map_err(|err| PagingError::from_map_error(page, err))?
+                .flush();
+        }
+
+        Ok(())
+    }
+}
+
+static KERNEL_SPACE: Mutex<Option<AddressSpace>> = Mutex::new(None);
+
+pub(super) fn init(mapper: OffsetPageTable<'static>) {
+    *KERNEL_SPACE.lock() = Some(AddressSpace::new(mapper));
+}
+
+/// Run `f` with exclusive access to the kernel address space.
+///
+/// `f` must not allocate on the heap, since growing the heap needs the
+/// address space too.
+pub fn with_kernel_space<R>(f: impl FnOnce(&mut AddressSpace) -> R) -> R {
+    without_interrupts(|| {
+        let mut space = KERNEL_SPACE.lock();
+        f(space
+            .as_mut()
+            .expect("memory management is not initialized"))
+    })
+}
+
+/// Returns whether `addr` is mapped in the kernel address space.
+///
+/// Unlike [`with_kernel_space`] this never blocks, so it can be used from
+/// panic and exception handlers. If the address space is locked or not
+/// initialized yet, `false` is returned.
+pub fn is_mapped(addr: VirtAddr) -> bool {
+    match KERNEL_SPACE.try_lock() {
+        Some(space) => space
+            .as_ref()
+            .is_some_and(|space| space.translate(addr).is_ok()),
+        None => false,
+    }
+}
+
+/// Returns the range of pages covering `[start, start + size)`.
+pub fn page_range(start: VirtAddr, size: u64) -> PageRange {
+    let start_page = Page::containing_address(start);
+    let end_page = Page::containing_address(start + size - 1u64);
+    Page::range(start_page, end_page + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::phys_to_virt;
+
+    const TEST_REGION_START: u64 = 0x_5555_0000_0000;
+
+    fn test_pages(first: u64, count: u64) -> PageRange {
+        page_range(
+            VirtAddr::new(TEST_REGION_START + first * 4096),
+            count * 4096,
+        )
+    }
+
+    #[test_case]
+    fn test_translate_identity_mapped_vga_buffer() {
+        let phys = with_kernel_space(|space| space.translate(VirtAddr::new(0xb8000)));
+        assert_eq!(phys, Ok(PhysAddr::new(0xb8000)));
+    }
+
+    #[test_case]
+    fn test_translate_physical_memory_window() {
+        let virt = phys_to_virt(PhysAddr::new(0x1234));
+        let phys = with_kernel_space(|space| space.translate(virt));
+        assert_eq!(phys, Ok(PhysAddr::new(0x1234)));
+    }
+
+    #[test_case]
+    fn test_translate_unmapped() {
+        let addr = VirtAddr::new(TEST_REGION_START);
+        let phys = with_kernel_space(|space| space.translate(addr));
+        assert_eq!(
+            phys,
+            Err(PagingError::PageNotMapped(Page::containing_address(addr)))
+        );
+    }
+
+    #[test_case]
+    fn test_map_write_unmap() {
+        let pages = test_pages(0, 4);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        with_kernel_space(|space| space.map_range(pages, flags)).expect("mapping failed");
+
+        let ptr: *mut u64 = pages.start.start_address().as_mut_ptr();
+        unsafe {
+            ptr.write_volatile(0xdead_beef);
+            assert_eq!(ptr.read_volatile(), 0xdead_beef);
+        }
+
+        with_kernel_space(|space| space.unmap_range(pages)).expect("unmapping failed");
+        let phys = with_kernel_space(|space| space.translate(pages.start.start_address()));
+        assert_eq!(phys, Err(PagingError::PageNotMapped(pages.start)));
+    }
+
+    #[test_case]
+    fn test_map_twice_fails_and_rolls_back() {
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        let mapped = test_pages(1, 1);
+        with_kernel_space(|space| space.map_range(mapped, flags)).expect("mapping failed");
+
+        let overlapping = test_pages(0, 3);
+        let result = with_kernel_space(|space| space.map_range(overlapping, flags));
+        assert_eq!(result, Err(PagingError::PageAlreadyMapped(mapped.start)));
+
+        // the page in front of the conflict must have been unmapped again
+        let phys = with_kernel_space(|space| space.translate(overlapping.start.start_address()));
+        assert_eq!(phys, Err(PagingError::PageNotMapped(overlapping.start)));
+
+        with_kernel_space(|space| space.unmap_range(mapped)).expect("unmapping failed");
+    }
+
+    #[test_case]
+    fn test_unmap_unmapped_fails() {
+        let pages = test_pages(0, 2);
+        let result = with_kernel_space(|space| space.unmap_range(pages));
+        assert_eq!(result, Err(PagingError::PageNotMapped(pages.start)));
+    }
+
+    #[test_case]
+    fn test_protect() {
+        let pages = test_pages(0, 2);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        with_kernel_space(|space| space.map_range(pages, flags)).expect("mapping failed");
+
+        let read_only = PageTableFlags::PRESENT | PageTableFlags::NO_EXECUTE;
+        with_kernel_space(|space| space.protect(pages, read_only)).expect("protect failed");
+        let new_flags = with_kernel_space(|space| space.flags(pages.start.start_address()));
+        assert_eq!(
+            new_flags.map(|f| f.contains(PageTableFlags::WRITABLE)),
+            Ok(false)
+        );
+        assert_eq!(
+            new_flags.map(|f| f.contains(PageTableFlags::NO_EXECUTE)),
+            Ok(true)
+        );
+
+        with_kernel_space(|space| space.unmap_range(pages)).expect("unmapping failed");
+    }
+}
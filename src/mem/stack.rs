@@ -0,0 +1,116 @@
+use x86_64::{structures::paging::PageTableFlags, VirtAddr};
+
+use super::paging::{self, PagingError};
+use super::vma::{self, Region, RegionKind, VmaError};
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Errors returned when allocating a kernel stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    Vma(VmaError),
+    Paging(PagingError),
+}
+
+/// A kernel stack with an unmapped guard page below it.
+///
+/// Overflowing the stack hits the guard page and causes a page fault,
+/// instead of silently overwriting whatever lies below.
+#[derive(Debug)]
+pub struct KernelStack {
+    region: Region,
+}
+
+impl KernelStack {
+    /// Returns the address of the guard page.
+    pub fn guard_page(&self) -> VirtAddr {
+        self.region.start
+    }
+
+    /// Returns the lowest usable address of the stack.
+    pub fn bottom(&self) -> VirtAddr {
+        self.region.start + PAGE_SIZE
+    }
+
+    /// Returns the initial stack pointer, i.e. the address right after
+    /// the end of the stack.
+    pub fn top(&self) -> VirtAddr {
+        self.region.end()
+    }
+
+    /// Returns the usable size of the stack in bytes.
+    pub fn size(&self) -> u64 {
+        self.region.size - PAGE_SIZE
+    }
+}
+
+/// Allocate a kernel stack of `pages` pages, plus a guard page.
+pub fn allocate_stack(pages: u64) -> Result<KernelStack, StackError> {
+    let region =
+        vma::allocate((pages + 1) * PAGE_SIZE, RegionKind::Stack).map_err(StackError::Vma)?;
+    let stack = KernelStack { region };
+
+    let pages = paging::page_range(stack.bottom(), stack.size());
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+    if let Err(err) = paging::with_kernel_space(|space| space.map_range(pages, flags)) {
+        vma::release(region.start).expect("failed to release a stack region");
+        return Err(StackError::Paging(err));
+    }
+
+    Ok(stack)
+}
+
+/// Unmap the stack and release its virtual memory.
+///
+/// # Safety
+///
+/// The caller must guarantee that the stack is not in use anymore.
+pub unsafe fn free_stack(stack: KernelStack) -> Result<(), StackError> {
+    let pages = paging::page_range(stack.bottom(), stack.size());
+    paging::with_kernel_space(|space| space.unmap_range(pages)).map_err(StackError::Paging)?;
+    vma::release(stack.region.start).map_err(StackError::Vma)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_stack_is_usable() {
+        let stack = allocate_stack(2).expect("stack allocation failed");
+        assert_eq!(stack.size(), 2 * PAGE_SIZE);
+
+        let top: *mut u64 = (stack.top() - 8u64).as_mut_ptr();
+        let bottom: *mut u64 = stack.bottom().as_mut_ptr();
+        unsafe {
+            top.write_volatile(1);
+            bottom.write_volatile(2);
+            assert_eq!(top.read_volatile(), 1);
+            assert_eq!(bottom.read_volatile(), 2);
+        }
+
+        unsafe { free_stack(stack) }.expect("freeing the stack failed");
+    }
+
+    #[test_case]
+    fn test_guard_page_is_unmapped() {
+        let stack = allocate_stack(1).expect("stack allocation failed");
+        let guard = stack.guard_page();
+
+        let result = paging::with_kernel_space(|space| space.translate(guard));
+        assert!(matches!(result, Err(PagingError::PageNotMapped(_))));
+
+        unsafe { free_stack(stack) }.expect("freeing the stack failed");
+    }
+
+    #[test_case]
+    fn test_freed_stack_releases_region() {
+        let stack = allocate_stack(1).expect("stack allocation failed");
+        let start = stack.guard_page();
+        unsafe { free_stack(stack) }.expect("freeing the stack failed");
+
+        assert_eq!(vma::find(start), None);
+    }
+}
@@ -0,0 +1,310 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use bootloader::bootinfo::MemoryMap;
+use spin::Mutex;
+use x86_64::{instructions::interrupts::without_interrupts, PhysAddr, VirtAddr};
+
+use super::{paging, phys_to_virt};
+use crate::allocator::{HEAP_MAX_SIZE, HEAP_START};
+
+const PAGE_SIZE: u64 = 4096;
+/// Size of the virtual memory covered by a single level 4 page table entry.
+const LEVEL_4_ENTRY_SIZE: u64 = 512 * 1024 * 1024 * 1024;
+
+/// Start of the kernel virtual memory out of which fresh ranges are handed out.
+pub const ARENA_START: u64 = 0x_6000_0000_0000;
+/// End (exclusive) of the kernel virtual memory out of which fresh ranges
+/// are handed out.
+pub const ARENA_END: u64 = 0x_7000_0000_0000;
+
+/// What a virtual memory area is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Mappings set up by the bootloader (kernel image, boot stack, ...).
+    Bootloader,
+    /// The window through which the complete physical memory is accessible.
+    PhysicalMemory,
+    Heap,
+    Stack,
+    Mmio,
+}
+
+/// A reserved range of virtual memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: VirtAddr,
+    pub size: u64,
+    pub kind: RegionKind,
+}
+
+impl Region {
+    /// Returns the first address after the region.
+    pub fn end(&self) -> VirtAddr {
+        self.start + self.size
+    }
+
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.start <= addr && addr < self.end()
+    }
+
+    pub fn pages(&self) -> paging::PageRange {
+        paging::page_range(self.start, self.size)
+    }
+
+    fn overlaps(&self, start: u64, size: u64) -> bool {
+        self.start.as_u64() < start + size && start < self.end().as_u64()
+    }
+}
+
+/// Errors returned by the [`VmaManager`] operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmaError {
+    /// The range is empty or not page aligned.
+    InvalidRange,
+    /// The range overlaps an already reserved region.
+    Overlap(Region),
+    /// There is no free range big enough in the arena.
+    OutOfVirtualMemory,
+    /// No region starts at the given address.
+    NotFound(VirtAddr),
+}
+
+/// Keeps track of reserved virtual memory areas.
+///
+/// Regions can either be reserved at fixed addresses, or allocated out of
+/// the `[ARENA_START, ARENA_END)` arena, which is guaranteed to not collide
+/// with anything reserved before.
+pub struct VmaManager {
+    /// Reserved regions keyed by their start address.
+    regions: BTreeMap<u64, Region>,
+    arena_start: u64,
+    arena_end: u64,
+}
+
+impl VmaManager {
+    pub const fn new(arena_start: u64, arena_end: u64) -> Self {
+        Self {
+            regions: BTreeMap::new(),
+            arena_start,
+            arena_end,
+        }
+    }
+
+    /// Reserve the region `[start, start + size)`.
+    pub fn reserve(
+        &mut self,
+        start: VirtAddr,
+        size: u64,
+        kind: RegionKind,
+    ) -> Result<Region, VmaError> {
+        if size == 0 || !start.is_aligned(PAGE_SIZE) || !size.is_multiple_of(PAGE_SIZE) {
+            return Err(VmaError::InvalidRange);
+        }
+        if let Some(region) = self.overlapping(start.as_u64(), size) {
+            return Err(VmaError::Overlap(region));
+        }
+
+        let region = Region { start, size, kind };
+        self.regions.insert(start.as_u64(), region);
+        Ok(region)
+    }
+
+    /// Reserve a fresh region of `size` bytes from the arena.
+    pub fn allocate(&mut self, size: u64, kind: RegionKind) -> Result<Region, VmaError> {
+        if size == 0 || !size.is_multiple_of(PAGE_SIZE) {
+            return Err(VmaError::InvalidRange);
+        }
+
+        let mut candidate = self.arena_start;
+        for region in self.regions.values() {
+            if region.end().as_u64() <= candidate {
+                continue;
+            }
+            if region.start.as_u64() >= candidate + size {
+                break;
+            }
+            candidate = region.end().as_u64();
+        }
+
+        if candidate + size > self.arena_end {
+            return Err(VmaError::OutOfVirtualMemory);
+        }
+        self.reserve(VirtAddr::new(candidate), size, kind)
+    }
+
+    /// Release the region starting at `start`.
+    ///
+    /// The caller is responsible for unmapping it first.
+    pub fn release(&mut self, start: VirtAddr) -> Result<Region, VmaError> {
+        self.regions
+            .remove(&start.as_u64())
+            .ok_or(VmaError::NotFound(start))
+    }
+
+    /// Returns the region containing `addr`, if any.
+    pub fn find(&self, addr: VirtAddr) -> Option<Region> {
+        self.regions
+            .range(..=addr.as_u64())
+            .next_back()
+            .map(|(_, region)| *region)
+            .filter(|region| region.contains(addr))
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.regions.values()
+    }
+
+    fn overlapping(&self, start: u64, size: u64) -> Option<Region> {
+        // Regions never overlap, so only the last region starting before
+        // the end of the range has to be checked.
+        self.regions
+            .range(..start + size)
+            .next_back()
+            .map(|(_, region)| *region)
+            .filter(|region| region.overlaps(start, size))
+    }
+}
+
+static VMA: Mutex<VmaManager> = Mutex::new(VmaManager::new(ARENA_START, ARENA_END));
+
+/// Register all regions which are already in use.
+///
+/// Must be called after the heap is initialized.
+pub fn init(memory_map: &MemoryMap) {
+    let physical_memory_end = memory_map
+        .iter()
+        .map(|region| region.range.end_addr())
+        .max()
+        .unwrap_or(0);
+    let physical_memory_size = align_up(physical_memory_end, PAGE_SIZE);
+
+    let used_level_4_entries = paging::with_kernel_space(|space| space.used_level_4_entries());
+
+    without_interrupts(|| {
+        let mut vma = VMA.lock();
+
+        vma.reserve(
+            phys_to_virt(PhysAddr::new(0)),
+            physical_memory_size,
+            RegionKind::PhysicalMemory,
+        )
+        .expect("failed to reserve the physical memory window");
+        vma.reserve(
+            VirtAddr::new(HEAP_START as u64),
+            HEAP_MAX_SIZE as u64,
+            RegionKind::Heap,
+        )
+        .expect("failed to reserve the heap");
+
+        // Everything else that is mapped at this point was set up by the
+        // bootloader. We don't know what exactly it is, so the whole
+        // level 4 entries are reserved. Only the lower half is considered,
+        // since that is where the arena and all fixed regions live.
+        for (index, _) in used_level_4_entries[..256]
+            .iter()
+            .enumerate()
+            .filter(|(_, &used)| used)
+        {
+            let start = VirtAddr::new(index as u64 * LEVEL_4_ENTRY_SIZE);
+            if vma
+                .overlapping(start.as_u64(), LEVEL_4_ENTRY_SIZE)
+                .is_none()
+            {
+                vma.reserve(start, LEVEL_4_ENTRY_SIZE, RegionKind::Bootloader)
+                    .expect("failed to reserve bootloader mappings");
+            }
+        }
+    });
+}
+
+/// Reserve the region `[start, start + size)`.
+pub fn reserve(start: VirtAddr, size: u64, kind: RegionKind) -> Result<Region, VmaError> {
+    without_interrupts(|| VMA.lock().reserve(start, size, kind))
+}
+
+/// Reserve a fresh region of `size` bytes, which doesn't overlap any other one.
+pub fn allocate(size: u64, kind: RegionKind) -> Result<Region, VmaError> {
+    without_interrupts(|| VMA.lock().allocate(size, kind))
+}
+
+/// Release the region starting at `start`.
+pub fn release(start: VirtAddr) -> Result<Region, VmaError> {
+    without_interrupts(|| VMA.lock().release(start))
+}
+
+/// Returns the region containing `addr`, if any.
+pub fn find(addr: VirtAddr) -> Option<Region> {
+    without_interrupts(|| VMA.lock().find(addr))
+}
+
+/// Returns a snapshot of all reserved regions, sorted by address.
+pub fn regions() -> Vec<Region> {
+    without_interrupts(|| VMA.lock().regions().copied().collect())
+}
+
+fn align_up(addr: u64, align: u64) -> u64 {
+    (addr + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_heap_is_reserved() {
+        let region = find(VirtAddr::new(HEAP_START as u64)).expect("heap is not reserved");
+        assert_eq!(region.kind, RegionKind::Heap);
+    }
+
+    #[test_case]
+    fn test_physical_memory_window_is_reserved() {
+        let region = find(phys_to_virt(PhysAddr::new(0x1000))).expect("window is not reserved");
+        assert_eq!(region.kind, RegionKind::PhysicalMemory);
+    }
+
+    #[test_case]
+    fn test_allocated_regions_do_not_overlap() {
+        let a = allocate(4 * PAGE_SIZE, RegionKind::Mmio).expect("allocation failed");
+        let b = allocate(PAGE_SIZE, RegionKind::Mmio).expect("allocation failed");
+
+        assert!(!a.overlaps(b.start.as_u64(), b.size));
+        assert!(a.start.as_u64() >= ARENA_START && a.end().as_u64() <= ARENA_END);
+        assert!(b.start.as_u64() >= ARENA_START && b.end().as_u64() <= ARENA_END);
+
+        release(a.start).expect("release failed");
+        release(b.start).expect("release failed");
+    }
+
+    #[test_case]
+    fn test_released_region_is_reused() {
+        let a = allocate(PAGE_SIZE, RegionKind::Stack).expect("allocation failed");
+        release(a.start).expect("release failed");
+        let b = allocate(PAGE_SIZE, RegionKind::Stack).expect("allocation failed");
+        release(b.start).expect("release failed");
+
+        assert_eq!(a.start, b.start);
+    }
+
+    #[test_case]
+    fn test_reserve_overlapping_fails() {
+        let a = allocate(2 * PAGE_SIZE, RegionKind::Mmio).expect("allocation failed");
+        let result = reserve(a.start + PAGE_SIZE, 2 * PAGE_SIZE, RegionKind::Mmio);
+        release(a.start).expect("release failed");
+
+        assert_eq!(result, Err(VmaError::Overlap(a)));
+    }
+
+    #[test_case]
+    fn test_invalid_ranges() {
+        assert_eq!(allocate(0, RegionKind::Mmio), Err(VmaError::InvalidRange));
+        assert_eq!(
+            allocate(PAGE_SIZE + 1, RegionKind::Mmio),
+            Err(VmaError::InvalidRange)
+        );
+        assert_eq!(
+            release(VirtAddr::new(ARENA_END)),
+            Err(VmaError::NotFound(VirtAddr::new(ARENA_END)))
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use x86_64::{
+    instructions::{interrupts, port::Port, tables::lidt},
+    structures::DescriptorTablePointer,
+    PhysAddr, VirtAddr,
+};
+
+use crate::acpi::{self, dsdt, AddressSpaceId};
+use crate::mem::phys_to_virt;
+use crate::{log, vga_println};
+
+/// `SCI_EN` bit of the PM1 control register, set while in ACPI mode.
+const PM1_SCI_ENABLE: u16 = 1 << 0;
+/// `SLP_EN` bit of the PM1 control register, which enters the sleep state.
+const PM1_SLEEP_ENABLE: u16 = 1 << 13;
+const PM1_SLEEP_TYPE_SHIFT: u16 = 10;
+
+const KEYBOARD_CONTROLLER_STATUS_PORT: u16 = 0x64;
+const KEYBOARD_CONTROLLER_INPUT_FULL: u8 = 1 << 1;
+/// Pulses the CPU reset line.
+const KEYBOARD_CONTROLLER_RESET: u8 = 0xfe;
+
+/// How often to poll for hardware before giving up.
+const POLL_ATTEMPTS: usize = 100_000;
+
+/// Turn the machine off through ACPI (sleep state S5).
+///
+/// If that isn't possible, the CPU is halted.
+pub fn shutdown() -> ! {
+    interrupts::disable();
+
+    if let Err(err) = acpi_shutdown() {
+        log!("ACPI shutdown failed: {}", err);
+    }
+
+    vga_println!("It is now safe to turn off your computer.");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Reset the machine.
+///
+/// Tries the ACPI reset register, then the 8042 keyboard controller and as
+/// a last resort causes a triple fault.
+pub fn reboot() -> ! {
+    interrupts::disable();
+
+    acpi_reset();
+    keyboard_controller_reset();
+    triple_fault()
+}
+
+fn acpi_shutdown() -> Result<(), &'static str> {
+    let tables = acpi::tables().ok_or("no ACPI tables")?;
+    let fadt = tables.fadt.ok_or("no FADT")?;
+    let dsdt = tables.dsdt.ok_or("no DSDT")?;
+    let sleep_type = dsdt::sleep_type(dsdt.data(), b"_S5_").ok_or("no S5 sleep state")?;
+    if fadt.pm1a_control_block == 0 {
+        return Err("no PM1a control block");
+    }
+
+    let mut pm1a = Port::<u16>::new(fadt.pm1a_control_block as u16);
+    // SAFETY: The port comes from the FADT.
+    unsafe {
+        if pm1a.read() & PM1_SCI_ENABLE == 0 {
+            enable_acpi_mode(&fadt, &mut pm1a)?;
+        }
+
+        pm1a.write(u16::from(sleep_type.pm1a) << PM1_SLEEP_TYPE_SHIFT | PM1_SLEEP_ENABLE);
+        if fadt.pm1b_control_block != 0 {
+            Port::<u16>::new(fadt.pm1b_control_block as u16)
+                .write(u16::from(sleep_type.pm1b) << PM1_SLEEP_TYPE_SHIFT | PM1_SLEEP_ENABLE);
+        }
+    }
+
+    // the machine should be off by now
+    for _ in 0..POLL_ATTEMPTS {
+        core::hint::spin_loop();
+    }
+    Err("the machine did not turn off")
+}
+
+/// Switch from legacy to ACPI mode.
+///
+/// # Safety
+///
+/// `pm1a` must be the PM1a control register from the FADT.
+unsafe fn enable_acpi_mode(
+    fadt: &acpi::fadt::Fadt,
+    pm1a: &mut Port<u16>,
+) -> Result<(), &'static str> {
+    if fadt.smi_command_port == 0 || fadt.acpi_enable == 0 {
+        return Err("ACPI mode can't be enabled");
+    }
+
+    unsafe {
+        Port::<u8>::new(fadt.smi_command_port as u16).write(fadt.acpi_enable);
+        for _ in 0..POLL_ATTEMPTS {
+            if pm1a.read() & PM1_SCI_ENABLE != 0 {
+                return Ok(());
+            }
+        }
+    }
+    Err("timed out enabling ACPI mode")
+}
+
+fn acpi_reset() {
+    let Some(fadt) = acpi::tables().and_then(|tables| tables.fadt) else {
+        return;
+    };
+    let Some(register) = fadt.reset_register else {
+        return;
+    };
+
+    // SAFETY: The register comes from the FADT.
+    unsafe {
+        match register.space {
+            AddressSpaceId::SystemIo => {
+                Port::<u8>::new(register.address as u16).write(fadt.reset_value);
+            }
+            AddressSpaceId::SystemMemory => {
+                let register: *mut u8 = phys_to_virt(PhysAddr::new(register.address)).as_mut_ptr();
+                register.write_volatile(fadt.reset_value);
+            }
+            // PCI configuration space is not supported
+            _ => return,
+        }
+    }
+
+    for _ in 0..POLL_ATTEMPTS {
+        core::hint::spin_loop();
+    }
+}
+
+fn keyboard_controller_reset() {
+    let mut port = Port::<u8>::new(KEYBOARD_CONTROLLER_STATUS_PORT);
+
+    // SAFETY: Writing to the 8042 only resets the machine, which is the goal.
+    unsafe {
+        for _ in 0..POLL_ATTEMPTS {
+            if port.read() & KEYBOARD_CONTROLLER_INPUT_FULL == 0 {
+                break;
+            }
+        }
+        port.write(KEYBOARD_CONTROLLER_RESET);
+    }
+
+    for _ in 0..POLL_ATTEMPTS {
+        core::hint::spin_loop();
+    }
+}
+
+/// Reset the CPU by raising an exception without a usable IDT.
+fn triple_fault() -> ! {
+    let empty = DescriptorTablePointer {
+        limit: 0,
+        base: VirtAddr::zero(),
+    };
+
+    // SAFETY: Nothing is supposed to run afterwards.
+    unsafe {
+        lidt(&empty);
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
@@ -0,0 +1,215 @@
+//! Fixed-capacity lock-free queue, which needs no allocation and can be
+//! used from interrupt handlers.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+struct Slot<T> {
+    /// Position of the item the slot is ready for, see [`RingBuffer`].
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Bounded lock-free queue of up to `N` items, with any number of
+/// producers and consumers.
+///
+/// Every slot has a sequence number. A slot can be written by the producer
+/// of item `position` once its sequence is `position` and read by the
+/// consumer of that item once it is `position + 1`. Afterwards the
+/// consumer sets it to `position + N`, for the next round.
+///
+/// Items pushed while the buffer is full are dropped and counted, see
+/// [`overflows`](RingBuffer::overflows).
+pub struct RingBuffer<T, const N: usize> {
+    slots: [Slot<T>; N],
+    /// Position of the next item to be read.
+    head: AtomicUsize,
+    /// Position of the next item to be written.
+    tail: AtomicUsize,
+    overflows: AtomicU64,
+}
+
+// SAFETY: Access to the items is synchronized with the sequences, each
+// item is moved from exactly one producer to one consumer.
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        assert!(N > 0, "ring buffer without capacity");
+
+        let mut slots = [const {
+            Slot {
+                sequence: AtomicUsize::new(0),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }; N];
+        let mut i = 0;
+        while i < N {
+            slots[i].sequence = AtomicUsize::new(i);
+            i += 1;
+        }
+
+        Self {
+            slots,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overflows: AtomicU64::new(0),
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Add `value` to the queue.
+    ///
+    /// If the queue is full, the overflow counter is incremented and
+    /// `value` is returned.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut position = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position % N];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+
+            match sequence.wrapping_sub(position) as isize {
+                0 => match self.tail.compare_exchange_weak(
+                    position,
+                    position.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: The slot was claimed by the exchange.
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.sequence
+                            .store(position.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => position = current,
+                },
+                // the slot still holds an item from the previous round
+                difference if difference < 0 => {
+                    self.overflows.fetch_add(1, Ordering::Relaxed);
+                    return Err(value);
+                }
+                _ => position = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Remove the oldest item.
+    pub fn pop(&self) -> Option<T> {
+        let mut position = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position % N];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+
+            match sequence.wrapping_sub(position.wrapping_add(1)) as isize {
+                0 => match self.head.compare_exchange_weak(
+                    position,
+                    position.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: The slot was written by a producer and
+                        // claimed by the exchange.
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.sequence
+                            .store(position.wrapping_add(N), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => position = current,
+                },
+                // the slot wasn't written yet
+                difference if difference < 0 => return None,
+                _ => position = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Returns an iterator removing items until the queue is empty.
+    pub fn drain(&self) -> impl Iterator<Item = T> + '_ {
+        core::iter::from_fn(|| self.pop())
+    }
+
+    /// Returns the number of items in the queue.
+    ///
+    /// Only a snapshot, if other code pushes or pops concurrently.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head).min(N)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of items dropped because the queue was full.
+    pub fn overflows(&self) -> u64 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+
+    use super::*;
+
+    #[test_case]
+    fn test_order_and_capacity() {
+        let ring = RingBuffer::<usize, 4>::new();
+        assert!(ring.is_empty());
+        for value in 0..4 {
+            assert_eq!(ring.push(value), Ok(()));
+        }
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.push(4), Err(4));
+        assert_eq!(ring.push(5), Err(5));
+        assert_eq!(ring.overflows(), 2);
+
+        assert_eq!(ring.pop(), Some(0));
+        assert_eq!(ring.push(6), Ok(()));
+        assert!(ring.drain().eq([1, 2, 3, 6]));
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test_case]
+    fn test_wrap_around() {
+        let ring = RingBuffer::<usize, 3>::new();
+        for value in 0..100 {
+            assert_eq!(ring.push(value), Ok(()));
+            assert_eq!(ring.push(value + 1000), Ok(()));
+            assert_eq!(ring.pop(), Some(value));
+            assert_eq!(ring.pop(), Some(value + 1000));
+        }
+        assert_eq!(ring.overflows(), 0);
+    }
+
+    #[test_case]
+    fn test_drop_releases_items() {
+        let value = Rc::new(());
+        {
+            let ring = RingBuffer::<Rc<()>, 2>::new();
+            ring.push(value.clone()).unwrap();
+            ring.push(value.clone()).unwrap();
+            assert_eq!(Rc::strong_count(&value), 3);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,115 @@
+use core::ptr;
+use core::str;
+
+/// Space reserved for the symbol table.
+const SYMTAB_SIZE: usize = 512 * 1024;
+const MAGIC: &[u8; 4] = b"KSYM";
+const HEADER_SIZE: usize = 8;
+const ENTRY_SIZE: usize = 16;
+
+/// Table of function symbols, filled in after linking by `tools/ksymtab`.
+///
+/// It is `mut`, so that the compiler can't assume it is all zeros. The
+/// format is described in the tool.
+#[used]
+#[link_section = ".ksymtab"]
+static mut SYMTAB: [u8; SYMTAB_SIZE] = [0; SYMTAB_SIZE];
+
+/// A function in the kernel image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    /// Demangled name, without the hash suffix.
+    pub name: &'static str,
+    pub address: u64,
+    pub size: u64,
+}
+
+impl Symbol {
+    pub fn contains(&self, address: u64) -> bool {
+        self.address <= address && address < self.address + self.size
+    }
+}
+
+fn table() -> &'static [u8] {
+    // SAFETY: The table is only ever written before the kernel is loaded.
+    unsafe { &*ptr::addr_of!(SYMTAB) }
+}
+
+fn read_u32(table: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(table[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(table: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(table[offset..offset + 8].try_into().unwrap())
+}
+
+/// Returns the number of symbols in the table.
+///
+/// This is 0 if the table was not filled in, e.g. because the kernel was
+/// booted without the runner from `.cargo/config.toml`.
+pub fn count() -> usize {
+    let table = table();
+    if &table[..4] != MAGIC {
+        return 0;
+    }
+    read_u32(table, 4) as usize
+}
+
+fn entry(index: usize) -> Symbol {
+    let table = table();
+    let entry = HEADER_SIZE + index * ENTRY_SIZE;
+    let names = HEADER_SIZE + count() * ENTRY_SIZE;
+
+    let name = &table[names + read_u32(table, entry + 12) as usize..];
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+
+    Symbol {
+        name: str::from_utf8(&name[..len]).unwrap_or("<invalid name>"),
+        address: read_u64(table, entry),
+        size: u64::from(read_u32(table, entry + 8)),
+    }
+}
+
+/// Returns the function containing `address`, if any.
+pub fn lookup(address: u64) -> Option<Symbol> {
+    // index of the first symbol starting after `address`
+    let (mut low, mut high) = (0, count());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if entry(mid).address <= address {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    low.checked_sub(1)
+        .map(entry)
+        .filter(|symbol| symbol.contains(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn some_function() -> u64 {
+        core::hint::black_box(42)
+    }
+
+    #[test_case]
+    fn test_lookup_function() {
+        assert!(count() > 0, "the symbol table is empty");
+
+        let address = some_function as fn() -> u64 as usize as u64;
+        let symbol = lookup(address + 1).expect("symbol not found");
+        assert_eq!(symbol.name, "os::symbols::tests::some_function");
+        assert_eq!(symbol.address, address);
+    }
+
+    #[test_case]
+    fn test_lookup_outside_of_functions() {
+        assert_eq!(lookup(0), None);
+        assert_eq!(lookup(u64::MAX), None);
+    }
+}
@@ -0,0 +1,273 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use x86_64::instructions::interrupts;
+
+use super::{Task, TaskId};
+use crate::ring_buffer::RingBuffer;
+
+/// Maximum number of tasks which can be woken at the same time, without
+/// falling back to checking every task.
+const TASK_QUEUE_SIZE: usize = 256;
+
+type TaskQueue = RingBuffer<TaskId, TASK_QUEUE_SIZE>;
+
+/// Runs tasks whenever they are woken, and halts the CPU while none is.
+///
+/// A task is queued at most once until it is polled again. If the queue
+/// is full anyway, the task is only marked as woken, and the executor looks
+/// for marked tasks once it notices the overflow.
+pub struct Executor {
+    tasks: BTreeMap<TaskId, Task>,
+    /// Tasks which were woken and have to be polled.
+    task_queue: Arc<TaskQueue>,
+    wakers: BTreeMap<TaskId, Arc<TaskWaker>>,
+    /// Overflows of the task queue which were already handled.
+    handled_overflows: u64,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor {
+            tasks: BTreeMap::new(),
+            task_queue: Arc::new(TaskQueue::new()),
+            wakers: BTreeMap::new(),
+            handled_overflows: 0,
+        }
+    }
+
+    /// Add a task, which is polled for the first time on the next run.
+    ///
+    /// # Panics
+    ///
+    /// If a task with the same ID was already spawned.
+    pub fn spawn(&mut self, task: Task) {
+        let id = task.id;
+        if self.tasks.insert(id, task).is_some() {
+            panic!("task with same ID already in tasks");
+        }
+        let waker = Arc::new(TaskWaker {
+            id,
+            queued: AtomicBool::new(false),
+            task_queue: self.task_queue.clone(),
+        });
+        waker.wake_task();
+        self.wakers.insert(id, waker);
+    }
+
+    /// Run tasks forever.
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            self.sleep_if_idle();
+        }
+    }
+
+    /// Run tasks until all of them completed.
+    pub fn run_until_done(&mut self) {
+        loop {
+            self.run_ready_tasks();
+            if self.tasks.is_empty() && !super::has_spawned() {
+                return;
+            }
+            self.sleep_if_idle();
+        }
+    }
+
+    /// Returns the number of tasks which haven't completed yet.
+    pub fn task_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn spawn_pending(&mut self) {
+        while let Some(task) = super::take_spawned() {
+            self.spawn(task);
+        }
+    }
+
+    fn run_ready_tasks(&mut self) {
+        self.spawn_pending();
+
+        loop {
+            while let Some(id) = self.task_queue.pop() {
+                self.poll_task(id);
+            }
+
+            if !self.has_overflowed() {
+                return;
+            }
+            self.handled_overflows = self.task_queue.overflows();
+            let woken: Vec<TaskId> = self
+                .wakers
+                .values()
+                .filter(|waker| waker.queued.load(Ordering::SeqCst))
+                .map(|waker| waker.id)
+                .collect();
+            for id in woken {
+                self.poll_task(id);
+            }
+        }
+    }
+
+    fn poll_task(&mut self, id: TaskId) {
+        // the task may have completed after it was woken
+        let Some(task) = self.tasks.get_mut(&id) else {
+            return;
+        };
+        let task_waker = &self.wakers[&id];
+        // wakeups from now on have to queue the task again
+        task_waker.queued.store(false, Ordering::SeqCst);
+        let waker = Waker::from(task_waker.clone());
+        let mut context = Context::from_waker(&waker);
+        if let Poll::Ready(()) = task.poll(&mut context) {
+            self.tasks.remove(&id);
+            self.wakers.remove(&id);
+        }
+    }
+
+    /// Returns whether tasks were woken while the task queue was full.
+    fn has_overflowed(&self) -> bool {
+        self.task_queue.overflows() != self.handled_overflows
+    }
+
+    /// Halt until the next interrupt, unless a task is ready.
+    ///
+    /// Interrupts are disabled while checking, so that a wakeup from an
+    /// interrupt handler can't slip in between the check and `hlt`.
+    fn sleep_if_idle(&self) {
+        interrupts::disable();
+        if self.task_queue.is_empty() && !self.has_overflowed() && !super::has_spawned() {
+            interrupts::enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct TaskWaker {
+    id: TaskId,
+    /// Set while the task waits to be polled.
+    queued: AtomicBool,
+    task_queue: Arc<TaskQueue>,
+}
+
+impl TaskWaker {
+    /// Queue the task, unless it is already waiting to be polled.
+    ///
+    /// Never blocks or fails, since it is called from interrupt handlers.
+    fn wake_task(&self) {
+        if !self.queued.swap(true, Ordering::SeqCst) {
+            // if the queue is full, the executor finds the task through
+            // the overflow counter and the flag
+            let _ = self.task_queue.push(self.id);
+        }
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_task();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+
+    use super::*;
+    use crate::time::timer::Timer;
+
+    #[test_case]
+    fn test_tasks_complete() {
+        static COMPLETED: AtomicUsize = AtomicUsize::new(0);
+
+        async fn number() -> usize {
+            21
+        }
+
+        let mut executor = Executor::new();
+        for _ in 0..3 {
+            executor.spawn(Task::new(async {
+                COMPLETED.fetch_add(number().await * 2, Ordering::SeqCst);
+            }));
+        }
+        assert_eq!(executor.task_count(), 3);
+
+        executor.run_until_done();
+        assert_eq!(COMPLETED.load(Ordering::SeqCst), 3 * 42);
+        assert_eq!(executor.task_count(), 0);
+    }
+
+    #[test_case]
+    fn test_waker_resumes_task() {
+        let mut executor = Executor::new();
+        let start = crate::time::ticks();
+        executor.spawn(Task::new(async move {
+            Timer::after(Duration::from_millis(3)).await;
+            assert!(crate::time::ticks() >= start + 3);
+        }));
+        executor.run_until_done();
+    }
+
+    #[test_case]
+    fn test_repeated_wakeups_queue_once() {
+        static POLLS: AtomicUsize = AtomicUsize::new(0);
+
+        let mut executor = Executor::new();
+        executor.spawn(Task::new(core::future::poll_fn(|cx| {
+            if POLLS.fetch_add(1, Ordering::SeqCst) > 0 {
+                return Poll::Ready(());
+            }
+            for _ in 0..2 * TASK_QUEUE_SIZE {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        })));
+        executor.run_until_done();
+        assert_eq!(POLLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test_case]
+    fn test_more_tasks_than_queue_slots() {
+        static COMPLETED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut executor = Executor::new();
+        for _ in 0..2 * TASK_QUEUE_SIZE {
+            executor.spawn(Task::new(async {
+                COMPLETED.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        executor.run_until_done();
+        assert_eq!(COMPLETED.load(Ordering::SeqCst), 2 * TASK_QUEUE_SIZE);
+    }
+
+    #[test_case]
+    fn test_spawn_from_task() {
+        static ORDER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut executor = Executor::new();
+        executor.spawn(Task::new(async {
+            super::super::spawn(async {
+                assert_eq!(ORDER.fetch_add(1, Ordering::SeqCst), 1);
+            });
+            assert_eq!(ORDER.fetch_add(1, Ordering::SeqCst), 0);
+        }));
+        executor.run_until_done();
+        assert_eq!(ORDER.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,159 @@
+//! Async streams of the scancodes and keys received from the keyboard.
+
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+use pc_keyboard::DecodedKey;
+
+use crate::io::keyboard;
+
+/// Waker of the task waiting for the next scancode.
+static WAKER: AtomicWaker = AtomicWaker::new();
+/// Whether a [`ScancodeStream`] exists.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Wake the task waiting for scancodes.
+///
+/// Called by the keyboard interrupt handler after queueing a scancode.
+pub(crate) fn wake() {
+    WAKER.wake();
+}
+
+/// Stream of the raw scancodes received from the keyboard.
+///
+/// Only one stream can exist at a time, since every scancode is returned
+/// only once.
+pub struct ScancodeStream {
+    _private: (),
+}
+
+impl ScancodeStream {
+    /// # Panics
+    ///
+    /// If another `ScancodeStream` exists.
+    pub fn new() -> Self {
+        assert!(
+            !TAKEN.swap(true, Ordering::Acquire),
+            "ScancodeStream already exists"
+        );
+        ScancodeStream { _private: () }
+    }
+}
+
+impl Default for ScancodeStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ScancodeStream {
+    fn drop(&mut self) {
+        TAKEN.store(false, Ordering::Release);
+    }
+}
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        // fast path, without registering the waker
+        if let Some(scancode) = keyboard::pop_scancode() {
+            return Poll::Ready(Some(scancode));
+        }
+
+        WAKER.register(cx.waker());
+        // a scancode may have arrived before the waker was registered
+        match keyboard::pop_scancode() {
+            Some(scancode) => {
+                WAKER.take();
+                Poll::Ready(Some(scancode))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Stream of the keys typed on the keyboard, decoded with the US layout.
+#[derive(Default)]
+pub struct KeyStream {
+    scancodes: ScancodeStream,
+}
+
+impl KeyStream {
+    /// # Panics
+    ///
+    /// If a [`ScancodeStream`] exists.
+    pub fn new() -> Self {
+        KeyStream {
+            scancodes: ScancodeStream::new(),
+        }
+    }
+}
+
+impl Stream for KeyStream {
+    type Item = DecodedKey;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<DecodedKey>> {
+        // Several scancodes can make up one key, so keep going until a key
+        // is complete or no scancodes are left.
+        while let Poll::Ready(scancode) = self.scancodes.poll_next_unpin(cx) {
+            let Some(scancode) = scancode else {
+                return Poll::Ready(None);
+            };
+            if let Some(key) = keyboard::decode(scancode) {
+                return Poll::Ready(Some(key));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::Waker;
+
+    use x86_64::instructions::interrupts::without_interrupts;
+
+    use super::*;
+
+    #[test_case]
+    fn test_scancode_stream() {
+        let mut stream = ScancodeStream::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        without_interrupts(|| {
+            keyboard::scancodes().for_each(drop);
+            keyboard::push_scancode(0x1e);
+            assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Ready(Some(0x1e)));
+            assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Pending);
+        });
+    }
+
+    #[test_case]
+    fn test_key_stream() {
+        let mut stream = KeyStream::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        without_interrupts(|| {
+            keyboard::scancodes().for_each(drop);
+            // press and release of B
+            keyboard::push_scancode(0x30);
+            keyboard::push_scancode(0xb0);
+            assert_eq!(
+                stream.poll_next_unpin(&mut cx),
+                Poll::Ready(Some(DecodedKey::Unicode('b')))
+            );
+            assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Pending);
+        });
+    }
+
+    #[test_case]
+    fn test_stream_is_released_on_drop() {
+        drop(KeyStream::new());
+        drop(ScancodeStream::new());
+        assert!(!TAKEN.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,72 @@
+//! Cooperative multitasking with async/await.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+pub mod executor;
+pub mod keyboard;
+
+pub use executor::Executor;
+
+/// Identifies a spawned task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A future running as an independent task.
+pub struct Task {
+    id: TaskId,
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl Task {
+    pub fn new(future: impl Future<Output = ()> + Send + 'static) -> Task {
+        Task {
+            id: TaskId::new(),
+            future: Box::pin(future),
+        }
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        self.future.as_mut().poll(context)
+    }
+}
+
+/// Tasks spawned with [`spawn`], which the executor hasn't picked up yet.
+static SPAWNED: Mutex<VecDeque<Task>> = Mutex::new(VecDeque::new());
+
+/// Run `future` as a new task on the executor.
+///
+/// Unlike [`Executor::spawn`] this can be called from anywhere, including
+/// other tasks and interrupt handlers.
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) -> TaskId {
+    let task = Task::new(future);
+    let id = task.id;
+    without_interrupts(|| SPAWNED.lock().push_back(task));
+    id
+}
+
+fn take_spawned() -> Option<Task> {
+    without_interrupts(|| SPAWNED.lock().pop_front())
+}
+
+fn has_spawned() -> bool {
+    without_interrupts(|| !SPAWNED.lock().is_empty())
+}
@@ -5,6 +5,8 @@ use core::panic::PanicInfo;
 use bootloader::{entry_point, BootInfo};
 use x86_64::instructions::port::Port;
 
+use crate::backtrace::{self, Backtrace};
+use crate::time::Instant;
 use crate::{hlt_loop, init, serial_print, serial_println};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -33,8 +35,9 @@ where
 {
     fn run(&self) {
         serial_print!("{}...\t", core::any::type_name::<T>());
+        let start = Instant::now();
         self();
-        serial_println!("[ok]");
+        serial_println!("[ok] ({:?})", start.elapsed());
     }
 }
 
@@ -51,6 +54,7 @@ pub fn test_runner(tests: &[&dyn Testable]) {
 pub fn test_panic_handler(info: &PanicInfo) -> ! {
     serial_println!("[failed]\n");
     serial_println!("Error: {}", info);
+    backtrace::print(&Backtrace::capture());
     exit_qemu(QemuExitCode::Failed);
 
     hlt_loop()
@@ -61,8 +65,8 @@ entry_point!(test_kernel_main);
 
 /// Entry point for `cargo test`
 #[cfg(test)]
-fn test_kernel_main(_: &'static BootInfo) -> ! {
-    init();
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
+    init(boot_info);
 
     crate::test_main();
 
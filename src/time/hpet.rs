@@ -0,0 +1,293 @@
+//! Driver for the High Precision Event Timer.
+
+use core::time::Duration;
+
+use spin::Once;
+use x86_64::PhysAddr;
+
+use crate::acpi::{self, AddressSpaceId};
+use crate::mem::mmio::{self, MmioError, MmioRegion};
+
+const SIZE: u64 = 0x400;
+const FEMTOS_PER_SEC: u64 = 1_000_000_000_000_000;
+const FEMTOS_PER_NANO: u128 = 1_000_000;
+
+/// Register offsets.
+mod register {
+    pub const CAPABILITIES: u64 = 0x000;
+    pub const CONFIGURATION: u64 = 0x010;
+    pub const MAIN_COUNTER: u64 = 0x0f0;
+
+    pub const fn comparator_configuration(comparator: u8) -> u64 {
+        0x100 + 0x20 * comparator as u64
+    }
+
+    pub const fn comparator_value(comparator: u8) -> u64 {
+        0x108 + 0x20 * comparator as u64
+    }
+
+    pub const CAPABILITIES_64_BIT: u64 = 1 << 13;
+    pub const CAPABILITIES_LEGACY_REPLACEMENT: u64 = 1 << 15;
+
+    pub const CONFIGURATION_ENABLE: u64 = 1 << 0;
+    /// Routes comparator 0 to IRQ 0 and comparator 1 to IRQ 8, in place
+    /// of the PIT and the RTC.
+    pub const CONFIGURATION_LEGACY_REPLACEMENT: u64 = 1 << 1;
+
+    pub const COMPARATOR_INTERRUPT_ENABLE: u64 = 1 << 2;
+    pub const COMPARATOR_PERIODIC: u64 = 1 << 3;
+    pub const COMPARATOR_PERIODIC_CAPABLE: u64 = 1 << 4;
+    /// Allows writing the accumulator of a periodic comparator.
+    pub const COMPARATOR_SET_VALUE: u64 = 1 << 6;
+}
+
+/// Errors returned when setting up the HPET.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpetError {
+    /// There is no ACPI HPET table.
+    NotFound,
+    /// The registers are not in system memory.
+    UnsupportedAddressSpace(AddressSpaceId),
+    Mmio(MmioError),
+    /// The capabilities register reports an invalid counter period.
+    InvalidPeriod(u64),
+}
+
+/// How a comparator raises interrupts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerMode {
+    /// Once, when the main counter reaches the given value.
+    OneShot { deadline: u64 },
+    /// Every `period` main counter ticks, starting one period from now.
+    Periodic { period: u64 },
+}
+
+/// The HPET, with its main counter running.
+pub struct Hpet {
+    mmio: MmioRegion,
+    /// Length of a main counter tick in femtoseconds.
+    period: u64,
+    comparators: u8,
+}
+
+impl Hpet {
+    /// Returns the length of a main counter tick in femtoseconds.
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Returns the frequency of the main counter in Hz.
+    pub fn frequency(&self) -> u64 {
+        FEMTOS_PER_SEC / self.period
+    }
+
+    pub fn comparators(&self) -> u8 {
+        self.comparators
+    }
+
+    pub fn is_64_bit(&self) -> bool {
+        self.capabilities() & register::CAPABILITIES_64_BIT != 0
+    }
+
+    /// Returns whether the comparators can replace the PIT and RTC
+    /// interrupts.
+    pub fn supports_legacy_replacement(&self) -> bool {
+        self.capabilities() & register::CAPABILITIES_LEGACY_REPLACEMENT != 0
+    }
+
+    /// Returns the value of the main counter.
+    pub fn counter(&self) -> u64 {
+        self.mmio.read_u64(register::MAIN_COUNTER)
+    }
+
+    /// Returns the time represented by `ticks` main counter ticks.
+    pub fn ticks_to_duration(&self, ticks: u64) -> Duration {
+        let nanos = u128::from(ticks) * u128::from(self.period) / FEMTOS_PER_NANO;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// Returns the number of main counter ticks in `duration`, rounded to
+    /// the nearest tick.
+    pub fn duration_to_ticks(&self, duration: Duration) -> u64 {
+        let femtos = duration.as_nanos() * FEMTOS_PER_NANO;
+        ((femtos + u128::from(self.period) / 2) / u128::from(self.period)) as u64
+    }
+
+    /// Returns the time since the main counter was started.
+    pub fn elapsed(&self) -> Duration {
+        self.ticks_to_duration(self.counter())
+    }
+
+    /// Route comparator 0 to IRQ 0 and comparator 1 to IRQ 8, which
+    /// disconnects the PIT and the RTC.
+    pub fn set_legacy_replacement(&self, enable: bool) {
+        let mut configuration = self.mmio.read_u64(register::CONFIGURATION);
+        if enable {
+            configuration |= register::CONFIGURATION_LEGACY_REPLACEMENT;
+        } else {
+            configuration &= !register::CONFIGURATION_LEGACY_REPLACEMENT;
+        }
+        self.mmio.write_u64(register::CONFIGURATION, configuration);
+    }
+
+    /// Start raising interrupts from `comparator`.
+    ///
+    /// Only comparators 0 and 1 are routed anywhere, and only in legacy
+    /// replacement mode.
+    ///
+    /// # Panics
+    ///
+    /// If the comparator doesn't exist, or doesn't support periodic mode
+    /// when asked for it.
+    pub fn start(&self, comparator: u8, mode: TimerMode) {
+        assert!(comparator < self.comparators, "no such HPET comparator");
+        let configuration_register = register::comparator_configuration(comparator);
+        let value_register = register::comparator_value(comparator);
+
+        let mut configuration = self.mmio.read_u64(configuration_register)
+            & !(register::COMPARATOR_PERIODIC | register::COMPARATOR_SET_VALUE);
+        configuration |= register::COMPARATOR_INTERRUPT_ENABLE;
+
+        match mode {
+            TimerMode::OneShot { deadline } => {
+                self.mmio.write_u64(configuration_register, configuration);
+                self.mmio.write_u64(value_register, deadline);
+            }
+            TimerMode::Periodic { period } => {
+                assert!(
+                    configuration & register::COMPARATOR_PERIODIC_CAPABLE != 0,
+                    "HPET comparator {} can't be periodic",
+                    comparator
+                );
+
+                // The counter is halted so that the first deadline can't
+                // pass before it is set.
+                let general = self.mmio.read_u64(register::CONFIGURATION);
+                self.mmio.write_u64(
+                    register::CONFIGURATION,
+                    general & !register::CONFIGURATION_ENABLE,
+                );
+
+                self.mmio.write_u64(
+                    configuration_register,
+                    configuration | register::COMPARATOR_PERIODIC | register::COMPARATOR_SET_VALUE,
+                );
+                // with SET_VALUE, the first write sets the deadline and the
+                // second one the period
+                self.mmio.write_u64(value_register, self.counter() + period);
+                self.mmio.write_u64(value_register, period);
+
+                self.mmio.write_u64(register::CONFIGURATION, general);
+            }
+        }
+    }
+
+    /// Stop raising interrupts from `comparator`.
+    pub fn stop(&self, comparator: u8) {
+        assert!(comparator < self.comparators, "no such HPET comparator");
+        let configuration_register = register::comparator_configuration(comparator);
+        let configuration = self.mmio.read_u64(configuration_register);
+        self.mmio.write_u64(
+            configuration_register,
+            configuration & !register::COMPARATOR_INTERRUPT_ENABLE,
+        );
+    }
+
+    fn capabilities(&self) -> u64 {
+        self.mmio.read_u64(register::CAPABILITIES)
+    }
+}
+
+static HPET: Once<Hpet> = Once::new();
+
+/// Map the HPET described by the ACPI tables and start its main counter.
+///
+/// All comparators are stopped.
+pub fn init() -> Result<&'static Hpet, HpetError> {
+    if let Some(hpet) = HPET.get() {
+        return Ok(hpet);
+    }
+
+    let table = acpi::tables()
+        .and_then(|tables| tables.hpet)
+        .ok_or(HpetError::NotFound)?;
+    if table.base_address.space != AddressSpaceId::SystemMemory {
+        return Err(HpetError::UnsupportedAddressSpace(table.base_address.space));
+    }
+
+    let base = PhysAddr::new(table.base_address.address);
+    // SAFETY: The address comes from the ACPI HPET table.
+    let mmio = unsafe { mmio::map(base, SIZE) }.map_err(HpetError::Mmio)?;
+
+    // the upper half holds the length of a counter tick, which can't be
+    // longer than 100 ns
+    let capabilities = mmio.read_u64(register::CAPABILITIES);
+    let period = capabilities >> 32;
+    if period == 0 || period > 100_000_000 {
+        mmio::unmap(mmio).map_err(HpetError::Mmio)?;
+        return Err(HpetError::InvalidPeriod(period));
+    }
+
+    let hpet = Hpet {
+        mmio,
+        period,
+        comparators: ((capabilities >> 8) & 0x1f) as u8 + 1,
+    };
+    for comparator in 0..hpet.comparators {
+        hpet.stop(comparator);
+    }
+    let configuration = hpet.mmio.read_u64(register::CONFIGURATION);
+    hpet.mmio.write_u64(
+        register::CONFIGURATION,
+        configuration | register::CONFIGURATION_ENABLE,
+    );
+
+    Ok(HPET.call_once(|| hpet))
+}
+
+/// Returns the HPET, if it was set up.
+pub fn get() -> Option<&'static Hpet> {
+    HPET.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_counter_advances() {
+        let hpet = get().expect("no HPET");
+        let first = hpet.counter();
+        let second = hpet.counter();
+        assert!(second > first);
+        assert!(hpet.frequency() >= 10_000_000);
+    }
+
+    #[test_case]
+    fn test_duration_conversion() {
+        let hpet = get().expect("no HPET");
+        let ticks = hpet.duration_to_ticks(Duration::from_millis(1));
+        assert_eq!(
+            hpet.ticks_to_duration(ticks).as_micros(),
+            Duration::from_millis(1).as_micros()
+        );
+    }
+
+    #[test_case]
+    fn test_elapsed_matches_tsc() {
+        let hpet = get().expect("no HPET");
+        let start = hpet.elapsed();
+        let instant = crate::time::Instant::now();
+        super::super::timer::sleep(Duration::from_millis(10));
+        let hpet_elapsed = hpet.elapsed() - start;
+        let tsc_elapsed = instant.elapsed();
+
+        let difference = hpet_elapsed.abs_diff(tsc_elapsed);
+        assert!(
+            difference < Duration::from_millis(1),
+            "HPET {:?}, TSC {:?}",
+            hpet_elapsed,
+            tsc_elapsed
+        );
+    }
+}
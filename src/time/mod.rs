@@ -0,0 +1,165 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use spin::Once;
+
+pub mod hpet;
+pub mod pit;
+pub mod rtc;
+pub mod system;
+pub mod timer;
+pub mod tsc;
+
+pub use system::SystemTime;
+pub use tsc::Instant;
+
+/// Frequency of the timer interrupt set up by [`init`].
+pub const TIMER_FREQUENCY: u32 = 1000;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+const FEMTOS_PER_SEC: u128 = 1_000_000_000_000_000;
+const FEMTOS_PER_NANO: u128 = 1_000_000;
+
+/// Number of timer interrupts since boot.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+/// Length of a tick in femtoseconds.
+static TICK_LENGTH: AtomicU64 = AtomicU64::new(0);
+static SOURCE: Once<TimerSource> = Once::new();
+
+/// The device raising the timer interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerSource {
+    Pit,
+    /// Comparator 0 of the HPET, in legacy replacement mode.
+    Hpet,
+}
+
+impl TimerSource {
+    /// Returns the preferred source on this machine: the HPET if the ACPI
+    /// tables describe one, the PIT otherwise.
+    ///
+    /// [`init`] still falls back to the PIT if the HPET turns out to be
+    /// unusable.
+    pub fn detect() -> Self {
+        match crate::acpi::tables().and_then(|tables| tables.hpet) {
+            Some(_) => TimerSource::Hpet,
+            None => TimerSource::Pit,
+        }
+    }
+}
+
+/// Start the timer interrupt with the given frequency, raised by `source`.
+///
+/// If the HPET is requested but unusable, the PIT is used instead. The
+/// actual frequency can differ slightly, since it has to be an integer
+/// fraction of the base frequency of the device. Use [`tick_duration`] to
+/// get the exact length of a tick.
+///
+/// Also calibrates the TSC and starts the wall clock, so interrupts must
+/// still be disabled.
+pub fn init(source: TimerSource, frequency: u32) {
+    let hpet = hpet::init();
+    tsc::calibrate();
+    system::init();
+
+    let source = match (source, hpet) {
+        (TimerSource::Hpet, Ok(hpet)) if hpet.supports_legacy_replacement() => {
+            let period =
+                (hpet.frequency() + u64::from(frequency) / 2) / u64::from(frequency.max(1));
+            TICK_LENGTH.store(period * hpet.period(), Ordering::SeqCst);
+            hpet.set_legacy_replacement(true);
+            hpet.start(0, hpet::TimerMode::Periodic { period });
+            TimerSource::Hpet
+        }
+        (source, hpet) => {
+            match (source, hpet) {
+                (TimerSource::Hpet, Ok(_)) => {
+                    crate::log!("HPET can't replace the PIT, using the PIT")
+                }
+                (TimerSource::Hpet, Err(err)) => {
+                    crate::log!("HPET unavailable ({:?}), using the PIT", err)
+                }
+                _ => {}
+            }
+            let divisor = pit::divisor(frequency);
+            let length = u128::from(divisor) * FEMTOS_PER_SEC / u128::from(pit::BASE_FREQUENCY);
+            TICK_LENGTH.store(length as u64, Ordering::SeqCst);
+            pit::set_divisor(divisor);
+            TimerSource::Pit
+        }
+    };
+    SOURCE.call_once(|| source);
+}
+
+/// Returns the source of the timer interrupt, if it was started.
+pub fn source() -> Option<TimerSource> {
+    SOURCE.get().copied()
+}
+
+/// Called on every timer interrupt.
+pub(crate) fn tick() {
+    let now = TICKS.fetch_add(1, Ordering::SeqCst) + 1;
+    timer::run_expired(now);
+}
+
+/// Returns the number of timer ticks since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::SeqCst)
+}
+
+/// Returns the time represented by `ticks` timer ticks.
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let length = u128::from(TICK_LENGTH.load(Ordering::SeqCst));
+    Duration::from_nanos((u128::from(ticks) * length / FEMTOS_PER_NANO) as u64)
+}
+
+/// Returns the number of timer ticks covering at least `duration`.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let length = u128::from(TICK_LENGTH.load(Ordering::SeqCst)).max(1);
+    (duration.as_nanos() * FEMTOS_PER_NANO).div_ceil(length) as u64
+}
+
+/// Returns the length of a single timer tick.
+pub fn tick_duration() -> Duration {
+    ticks_to_duration(1)
+}
+
+/// Returns the time since the timer was started, with tick granularity.
+pub fn uptime() -> Duration {
+    ticks_to_duration(ticks())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_tick_duration() {
+        let expected = match source() {
+            // 1193 / 1193182 Hz
+            Some(TimerSource::Pit) => Duration::from_nanos(999_847),
+            Some(TimerSource::Hpet) => Duration::from_millis(1),
+            None => panic!("timer not started"),
+        };
+        assert!(tick_duration().abs_diff(expected) < Duration::from_micros(1));
+    }
+
+    #[test_case]
+    fn test_duration_to_ticks() {
+        assert_eq!(duration_to_ticks(Duration::ZERO), 0);
+        assert_eq!(duration_to_ticks(tick_duration()), 1);
+        if source() == Some(TimerSource::Pit) {
+            assert_eq!(duration_to_ticks(Duration::from_millis(10)), 11);
+            assert_eq!(duration_to_ticks(Duration::from_secs(1)), 1001);
+        }
+    }
+
+    #[test_case]
+    fn test_ticks_advance() {
+        let start = ticks();
+        while ticks() < start + 2 {
+            x86_64::instructions::hlt();
+        }
+        assert!(uptime() >= ticks_to_duration(start + 2));
+    }
+}
@@ -0,0 +1,47 @@
+use x86_64::instructions::port::Port;
+
+/// Frequency of the oscillator driving the PIT.
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+
+const CHANNEL_0_DATA_PORT: u16 = 0x40;
+const COMMAND_PORT: u16 = 0x43;
+/// Channel 0, low byte then high byte, mode 2 (rate generator), binary.
+const COMMAND_CHANNEL_0_RATE_GENERATOR: u8 = 0x34;
+
+/// Returns the reload value closest to the requested frequency.
+///
+/// The PIT can't go below ~18.2 Hz or above its base frequency, so the
+/// result is clamped.
+pub fn divisor(frequency: u32) -> u32 {
+    let frequency = frequency.max(1);
+    ((BASE_FREQUENCY + frequency / 2) / frequency).clamp(1, 0x1_0000)
+}
+
+/// Program channel 0 to fire IRQ 0 every `divisor` oscillator cycles.
+pub fn set_divisor(divisor: u32) {
+    assert!((1..=0x1_0000).contains(&divisor), "invalid PIT divisor");
+    // a reload value of 0 stands for 65536
+    let reload = (divisor & 0xffff) as u16;
+
+    let mut command = Port::<u8>::new(COMMAND_PORT);
+    let mut data = Port::<u8>::new(CHANNEL_0_DATA_PORT);
+    // SAFETY: Only reprograms the PIT, which nothing else uses.
+    unsafe {
+        command.write(COMMAND_CHANNEL_0_RATE_GENERATOR);
+        data.write(reload as u8);
+        data.write((reload >> 8) as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_divisor() {
+        assert_eq!(divisor(1000), 1193);
+        assert_eq!(divisor(100), 11932);
+        assert_eq!(divisor(1), 0x1_0000);
+        assert_eq!(divisor(u32::MAX), 1);
+    }
+}
@@ -0,0 +1,309 @@
+//! Driver for the real-time clock in the CMOS.
+
+use core::fmt;
+
+use x86_64::instructions::{interrupts::without_interrupts, port::Port};
+
+use crate::acpi;
+
+const INDEX_PORT: u16 = 0x70;
+const DATA_PORT: u16 = 0x71;
+
+/// CMOS register indices.
+mod register {
+    pub const SECONDS: u8 = 0x00;
+    pub const MINUTES: u8 = 0x02;
+    pub const HOURS: u8 = 0x04;
+    pub const DAY: u8 = 0x07;
+    pub const MONTH: u8 = 0x08;
+    pub const YEAR: u8 = 0x09;
+    pub const STATUS_A: u8 = 0x0a;
+    pub const STATUS_B: u8 = 0x0b;
+
+    /// Set while the clock updates its registers, which then must not be
+    /// read.
+    pub const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+    pub const STATUS_B_24_HOUR: u8 = 1 << 1;
+    pub const STATUS_B_BINARY: u8 = 1 << 2;
+    /// Set in the hours register for PM times in 12 hour mode.
+    pub const HOURS_PM: u8 = 1 << 7;
+}
+
+/// Century assumed if the FADT doesn't name a century
+/// register.
+const DEFAULT_CENTURY: u16 = 20;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A calendar date and time of day, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Returns the number of seconds since 1970-01-01 00:00:00.
+    pub fn to_unix(&self) -> u64 {
+        // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        days as u64 * SECONDS_PER_DAY
+            + u64::from(self.hour) * 3600
+            + u64::from(self.minute) * 60
+            + u64::from(self.second)
+    }
+
+    /// Returns the date and time `seconds` after 1970-01-01 00:00:00.
+    pub fn from_unix(seconds: u64) -> Self {
+        // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = (seconds / SECONDS_PER_DAY) as i64 + 719_468;
+        let time = seconds % SECONDS_PER_DAY;
+
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// The clock registers as stored in the CMOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Registers {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    /// Raw century register, if there is one.
+    century: Option<u8>,
+}
+
+impl Registers {
+    /// Convert the registers to a date, according to the format selected
+    /// in status register B.
+    fn decode(&self, status_b: u8) -> DateTime {
+        let binary = status_b & register::STATUS_B_BINARY != 0;
+        let decode = |value: u8| if binary { value } else { from_bcd(value) };
+
+        let pm = self.hour & register::HOURS_PM != 0;
+        let mut hour = decode(self.hour & !register::HOURS_PM);
+        if status_b & register::STATUS_B_24_HOUR == 0 {
+            // 12 AM is midnight, 12 PM is noon
+            hour = match (hour, pm) {
+                (12, false) => 0,
+                (12, true) => 12,
+                (hour, false) => hour,
+                (hour, true) => hour + 12,
+            };
+        }
+
+        let century = self
+            .century
+            .map_or(DEFAULT_CENTURY, |century| u16::from(decode(century)));
+
+        DateTime {
+            year: century * 100 + u16::from(decode(self.year)),
+            month: decode(self.month),
+            day: decode(self.day),
+            hour,
+            minute: decode(self.minute),
+            second: decode(self.second),
+        }
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+/// Read a CMOS register.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled, so that nothing else selects
+/// a different register in between.
+unsafe fn read_register(index: u8) -> u8 {
+    unsafe {
+        Port::<u8>::new(INDEX_PORT).write(index);
+        Port::<u8>::new(DATA_PORT).read()
+    }
+}
+
+/// Read the clock registers once no update is in progress.
+///
+/// # Safety
+///
+/// See [`read_register`].
+unsafe fn read_registers(century_register: Option<u8>) -> Registers {
+    unsafe {
+        while read_register(register::STATUS_A) & register::STATUS_A_UPDATE_IN_PROGRESS != 0 {
+            core::hint::spin_loop();
+        }
+
+        Registers {
+            second: read_register(register::SECONDS),
+            minute: read_register(register::MINUTES),
+            hour: read_register(register::HOURS),
+            day: read_register(register::DAY),
+            month: read_register(register::MONTH),
+            year: read_register(register::YEAR),
+            century: century_register.map(|index| read_register(index)),
+        }
+    }
+}
+
+/// Read the current date and time from the RTC.
+///
+/// The RTC is assumed to run in UTC.
+pub fn read() -> DateTime {
+    let century_register = acpi::tables()
+        .and_then(|tables| tables.fadt)
+        .map(|fadt| fadt.century_register)
+        .filter(|&index| index != 0);
+
+    // SAFETY: Interrupts are disabled.
+    without_interrupts(|| unsafe {
+        // An update could start right after the check of the update in
+        // progress flag, so read until two reads agree.
+        let mut registers = read_registers(century_register);
+        loop {
+            let again = read_registers(century_register);
+            if again == registers {
+                break;
+            }
+            registers = again;
+        }
+
+        registers.decode(read_register(register::STATUS_B))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn registers(hour: u8) -> Registers {
+        Registers {
+            second: 0x59,
+            minute: 0x30,
+            hour,
+            day: 0x18,
+            month: 0x10,
+            year: 0x26,
+            century: None,
+        }
+    }
+
+    #[test_case]
+    fn test_decode_bcd() {
+        let date = registers(0x23).decode(register::STATUS_B_24_HOUR);
+        assert_eq!(
+            date,
+            DateTime {
+                year: 2026,
+                month: 10,
+                day: 18,
+                hour: 23,
+                minute: 30,
+                second: 59,
+            }
+        );
+
+        let mut century = registers(0x23);
+        century.century = Some(0x19);
+        assert_eq!(century.decode(register::STATUS_B_24_HOUR).year, 1926);
+    }
+
+    #[test_case]
+    fn test_decode_binary() {
+        let registers = Registers {
+            second: 59,
+            minute: 30,
+            hour: 23,
+            day: 18,
+            month: 10,
+            year: 26,
+            century: Some(20),
+        };
+        let date = registers.decode(register::STATUS_B_BINARY | register::STATUS_B_24_HOUR);
+        assert_eq!(date.to_unix(), 1_792_366_259);
+    }
+
+    #[test_case]
+    fn test_decode_12_hour() {
+        let pm = register::HOURS_PM;
+        assert_eq!(registers(0x12).decode(0).hour, 0);
+        assert_eq!(registers(0x01).decode(0).hour, 1);
+        assert_eq!(registers(0x12 | pm).decode(0).hour, 12);
+        assert_eq!(registers(0x11 | pm).decode(0).hour, 23);
+    }
+
+    #[test_case]
+    fn test_unix_conversion() {
+        let epoch = DateTime::from_unix(0);
+        assert_eq!(epoch.to_string(), "1970-01-01 00:00:00");
+        assert_eq!(epoch.to_unix(), 0);
+
+        let leap_day = DateTime {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 12,
+            minute: 0,
+            second: 1,
+        };
+        assert_eq!(leap_day.to_unix(), 1_709_208_001);
+        assert_eq!(DateTime::from_unix(1_709_208_001), leap_day);
+    }
+
+    #[test_case]
+    fn test_read() {
+        let date = read();
+        assert!(date.year >= 2020, "RTC reports {}", date);
+        assert!((1..=12).contains(&date.month));
+        assert!((1..=31).contains(&date.day));
+    }
+}
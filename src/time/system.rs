@@ -0,0 +1,114 @@
+use core::fmt;
+use core::ops::{Add, Sub};
+use core::time::Duration;
+
+use spin::Once;
+
+use super::rtc::{self, DateTime};
+use super::Instant;
+
+/// Wall-clock time read from the RTC at boot, and the instant it was read.
+static BOOT_TIME: Once<(Duration, Instant)> = Once::new();
+
+/// A point in wall-clock time, measured from the Unix epoch.
+///
+/// The RTC is only read once at boot, afterwards the time advances with the
+/// TSC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SystemTime(Duration);
+
+impl SystemTime {
+    /// 1970-01-01 00:00:00 UTC.
+    pub const UNIX_EPOCH: SystemTime = SystemTime(Duration::ZERO);
+
+    /// Returns the current time.
+    ///
+    /// Before [`init`] was called, this is the uptime counted from the
+    /// epoch.
+    pub fn now() -> Self {
+        match BOOT_TIME.get() {
+            Some((boot_time, boot_instant)) => Self(*boot_time + boot_instant.elapsed()),
+            None => Self(super::uptime()),
+        }
+    }
+
+    /// Returns the time elapsed since `earlier`, or `Err` with the
+    /// difference if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: SystemTime) -> Result<Duration, Duration> {
+        self.0
+            .checked_sub(earlier.0)
+            .ok_or_else(|| earlier.0 - self.0)
+    }
+
+    /// Returns the time since the Unix epoch.
+    pub fn unix_time(&self) -> Duration {
+        self.0
+    }
+
+    /// Returns the calendar date and time, rounded down to the second.
+    pub fn date_time(&self) -> DateTime {
+        DateTime::from_unix(self.0.as_secs())
+    }
+}
+
+impl Add<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn add(self, duration: Duration) -> SystemTime {
+        SystemTime(self.0 + duration)
+    }
+}
+
+impl Sub<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn sub(self, duration: Duration) -> SystemTime {
+        SystemTime(self.0 - duration)
+    }
+}
+
+impl fmt::Display for SystemTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:03}", self.date_time(), self.0.subsec_millis())
+    }
+}
+
+/// Read the RTC and start the wall clock.
+///
+/// The TSC must already be calibrated.
+pub fn init() {
+    BOOT_TIME.call_once(|| {
+        let date_time = rtc::read();
+        (Duration::from_secs(date_time.to_unix()), Instant::now())
+    });
+}
+
+/// Returns the time the kernel booted, if the wall clock was started.
+pub fn boot_time() -> Option<SystemTime> {
+    BOOT_TIME.get().map(|&(boot_time, _)| SystemTime(boot_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_now_advances() {
+        let boot = boot_time().expect("wall clock not started");
+        let first = SystemTime::now();
+        super::super::timer::sleep(Duration::from_millis(5));
+        let second = SystemTime::now();
+
+        assert!(first >= boot);
+        let elapsed = second.duration_since(first).unwrap();
+        assert!(elapsed >= Duration::from_millis(4), "{:?}", elapsed);
+        assert!(first.duration_since(second).is_err());
+    }
+
+    #[test_case]
+    fn test_date_time() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_709_208_001_250);
+        assert_eq!(time.date_time().to_unix(), 1_709_208_001);
+        assert_eq!(time.unix_time().subsec_millis(), 250);
+    }
+}
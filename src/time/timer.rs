@@ -0,0 +1,388 @@
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::sync::Arc;
+use core::cmp::Reverse;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use spin::Mutex;
+use x86_64::instructions::interrupts::{self, without_interrupts};
+
+use super::{duration_to_ticks, ticks};
+
+/// Identifies a registered timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimerId(u64);
+
+impl TimerId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+enum Callback {
+    Once(Box<dyn FnOnce() + Send>),
+    Periodic {
+        period: u64,
+        callback: Box<dyn FnMut() + Send>,
+    },
+}
+
+/// Pending timers, ordered by their deadline.
+struct TimerQueue {
+    /// `(deadline, id)` of every timer. Entries of cancelled timers are
+    /// skipped once they expire.
+    deadlines: BinaryHeap<Reverse<(u64, TimerId)>>,
+    /// Callbacks of pending timers. The callback of a periodic timer is
+    /// `None` while it runs, and removing the entry cancels the timer.
+    callbacks: BTreeMap<TimerId, Option<Callback>>,
+}
+
+impl TimerQueue {
+    const fn new() -> Self {
+        Self {
+            deadlines: BinaryHeap::new(),
+            callbacks: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, id: TimerId, deadline: u64, callback: Callback) {
+        self.deadlines.push(Reverse((deadline, id)));
+        self.callbacks.insert(id, Some(callback));
+    }
+
+    /// Remove the next timer which expired at `now`.
+    ///
+    /// Periodic timers keep an empty entry until they are rescheduled with
+    /// [`reschedule`](Self::reschedule).
+    fn pop_expired(&mut self, now: u64) -> Option<(TimerId, u64, Callback)> {
+        while let Some(&Reverse((deadline, id))) = self.deadlines.peek() {
+            if deadline > now {
+                break;
+            }
+            self.deadlines.pop();
+            let callback = match self.callbacks.remove(&id) {
+                Some(Some(callback)) => callback,
+                _ => continue,
+            };
+            if let Callback::Periodic { .. } = callback {
+                self.callbacks.insert(id, None);
+            }
+            return Some((id, deadline, callback));
+        }
+        None
+    }
+
+    /// Schedule a periodic timer again after its callback ran.
+    ///
+    /// Returns the callback if the timer was cancelled in the meantime.
+    fn reschedule(&mut self, id: TimerId, deadline: u64, callback: Callback) -> Option<Callback> {
+        match self.callbacks.get(&id) {
+            Some(None) => {
+                self.insert(id, deadline, callback);
+                None
+            }
+            _ => Some(callback),
+        }
+    }
+}
+
+static TIMERS: Mutex<TimerQueue> = Mutex::new(TimerQueue::new());
+
+fn schedule(deadline: u64, callback: Callback) -> TimerId {
+    let id = TimerId::new();
+    without_interrupts(|| TIMERS.lock().insert(id, deadline, callback));
+    id
+}
+
+/// Returns the first tick at which at least `delay` has passed.
+///
+/// The current tick is already partly over, so it doesn't count.
+fn deadline_after(delay: Duration) -> u64 {
+    ticks() + duration_to_ticks(delay) + 1
+}
+
+/// Call `callback` once at tick `deadline`.
+///
+/// Callbacks run in the timer interrupt handler, so they must be short
+/// and must not block.
+pub fn at_tick(deadline: u64, callback: impl FnOnce() + Send + 'static) -> TimerId {
+    schedule(deadline, Callback::Once(Box::new(callback)))
+}
+
+/// Call `callback` once after `delay`.
+///
+/// See [`at_tick`] for the restrictions on callbacks.
+pub fn after(delay: Duration, callback: impl FnOnce() + Send + 'static) -> TimerId {
+    at_tick(deadline_after(delay), callback)
+}
+
+/// Call `callback` every `period`, until the timer is cancelled.
+///
+/// Periods shorter than a tick are rounded up to one tick. See [`at_tick`]
+/// for the restrictions on callbacks.
+pub fn every(period: Duration, callback: impl FnMut() + Send + 'static) -> TimerId {
+    // with a period of 0 the timer would expire again right away and the
+    // interrupt handler would never return
+    let period = duration_to_ticks(period).max(1);
+    schedule(
+        ticks() + period,
+        Callback::Periodic {
+            period,
+            callback: Box::new(callback),
+        },
+    )
+}
+
+/// Cancel a timer. Returns `false` if it already expired or was cancelled.
+///
+/// A periodic timer can also be cancelled from its own callback.
+pub fn cancel(id: TimerId) -> bool {
+    without_interrupts(|| TIMERS.lock().callbacks.remove(&id).is_some())
+}
+
+/// Run the callbacks of all timers which expired at tick `now`.
+///
+/// Called from the timer interrupt handler.
+pub(super) fn run_expired(now: u64) {
+    // The lock is released while running a callback, so that it can
+    // register or cancel timers itself.
+    loop {
+        let Some((id, deadline, callback)) = TIMERS.lock().pop_expired(now) else {
+            break;
+        };
+        match callback {
+            Callback::Once(callback) => callback(),
+            Callback::Periodic {
+                period,
+                mut callback,
+            } => {
+                callback();
+                let cancelled = TIMERS.lock().reschedule(
+                    id,
+                    deadline + period,
+                    Callback::Periodic { period, callback },
+                );
+                // the callback is dropped outside of the lock
+                drop(cancelled);
+            }
+        }
+    }
+}
+
+/// Block for at least `duration`, halting the CPU in the meantime.
+///
+/// Must be called with interrupts enabled, since only the timer interrupt
+/// can end the wait.
+pub fn sleep(duration: Duration) {
+    assert!(
+        interrupts::are_enabled(),
+        "sleep called with interrupts disabled"
+    );
+
+    let deadline = deadline_after(duration);
+    while ticks() < deadline {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// A future which completes once a deadline has passed.
+pub struct Timer {
+    deadline: u64,
+    /// The registered wakeup, set on the first poll.
+    wakeup: Option<(TimerId, Arc<Mutex<Waker>>)>,
+}
+
+impl Timer {
+    /// Returns a future which completes after `delay`.
+    pub fn after(delay: Duration) -> Self {
+        Self::at_tick(deadline_after(delay))
+    }
+
+    /// Returns a future which completes at tick `deadline`.
+    pub fn at_tick(deadline: u64) -> Self {
+        Self {
+            deadline,
+            wakeup: None,
+        }
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if ticks() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        match &self.wakeup {
+            Some((_, waker)) => without_interrupts(|| {
+                let mut waker = waker.lock();
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+            }),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let id = at_tick(self.deadline, {
+                    let waker = waker.clone();
+                    move || waker.lock().wake_by_ref()
+                });
+                self.wakeup = Some((id, waker));
+            }
+        }
+
+        // The deadline may have passed right before the wakeup was set up.
+        if ticks() >= self.deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let Some((id, _)) = self.wakeup {
+            cancel(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicBool, AtomicUsize};
+
+    use super::*;
+    use crate::time::Instant;
+
+    #[test_case]
+    fn test_one_shot() {
+        let fired = Arc::new(AtomicBool::new(false));
+        after(Duration::from_millis(2), {
+            let fired = fired.clone();
+            move || fired.store(true, Ordering::SeqCst)
+        });
+
+        assert!(!fired.load(Ordering::SeqCst));
+        sleep(Duration::from_millis(5));
+        assert!(fired.load(Ordering::SeqCst));
+    }
+
+    #[test_case]
+    fn test_cancel() {
+        let fired = Arc::new(AtomicBool::new(false));
+        let id = after(Duration::from_millis(2), {
+            let fired = fired.clone();
+            move || fired.store(true, Ordering::SeqCst)
+        });
+
+        assert!(cancel(id));
+        sleep(Duration::from_millis(5));
+        assert!(!fired.load(Ordering::SeqCst));
+        assert!(!cancel(id));
+    }
+
+    #[test_case]
+    fn test_periodic() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let id = every(Duration::from_millis(1), {
+            let count = count.clone();
+            move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        sleep(Duration::from_millis(10));
+        assert!(cancel(id));
+        let fired = count.load(Ordering::SeqCst);
+        assert!(fired >= 5, "fired only {} times", fired);
+
+        sleep(Duration::from_millis(3));
+        assert_eq!(count.load(Ordering::SeqCst), fired);
+    }
+
+    #[test_case]
+    fn test_zero_period() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let start = ticks();
+        let id = every(Duration::ZERO, {
+            let count = count.clone();
+            move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        sleep(Duration::from_millis(5));
+        assert!(cancel(id));
+        // at most once per tick
+        let fired = count.load(Ordering::SeqCst) as u64;
+        assert!(fired >= 1);
+        assert!(fired <= ticks() - start, "fired {} times", fired);
+    }
+
+    #[test_case]
+    fn test_periodic_cancels_itself() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let id = Arc::new(Mutex::new(None));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let timer = every(Duration::from_millis(1), {
+            let count = count.clone();
+            let id = id.clone();
+            let cancelled = cancelled.clone();
+            move || {
+                if count.fetch_add(1, Ordering::SeqCst) == 2 {
+                    let id = id.lock().expect("timer id not set");
+                    cancelled.store(cancel(id), Ordering::SeqCst);
+                }
+            }
+        });
+        without_interrupts(|| *id.lock() = Some(timer));
+
+        sleep(Duration::from_millis(10));
+        assert!(cancelled.load(Ordering::SeqCst));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(!cancel(timer));
+    }
+
+    #[test_case]
+    fn test_sleep() {
+        for duration in [Duration::from_micros(100), Duration::from_millis(10)] {
+            let start = Instant::now();
+            sleep(duration);
+            let elapsed = start.elapsed();
+            assert!(elapsed >= duration, "slept {:?} of {:?}", elapsed, duration);
+        }
+    }
+
+    #[test_case]
+    fn test_timer_future() {
+        let start = Instant::now();
+        let mut timer = Timer::after(Duration::from_millis(3));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        while Pin::new(&mut timer).poll(&mut cx).is_pending() {
+            x86_64::instructions::hlt();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(3));
+    }
+
+    #[test_case]
+    fn test_after_waits_full_delay() {
+        let start = Instant::now();
+        let elapsed = Arc::new(Mutex::new(None));
+        after(Duration::from_micros(500), {
+            let elapsed = elapsed.clone();
+            move || *elapsed.lock() = Some(start.elapsed())
+        });
+
+        sleep(Duration::from_millis(3));
+        let elapsed = without_interrupts(|| *elapsed.lock()).expect("timer didn't fire");
+        assert!(elapsed >= Duration::from_micros(500), "{:?}", elapsed);
+    }
+}
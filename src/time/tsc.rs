@@ -0,0 +1,234 @@
+use core::arch::x86_64::{__cpuid, _rdtsc};
+use core::ops::{Add, Sub};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use spin::Once;
+use x86_64::instructions::port::Port;
+
+use super::{pit, NANOS_PER_SEC};
+
+const CPUID_EXTENDED_MAX_LEAF: u32 = 0x8000_0000;
+const CPUID_ADVANCED_POWER_MANAGEMENT: u32 = 0x8000_0007;
+const CPUID_APM_EDX_INVARIANT_TSC: u32 = 1 << 8;
+
+/// How long to measure the TSC against the reference clock.
+const CALIBRATION_TIME: Duration = Duration::from_millis(10);
+
+/// Frequency of the TSC in Hz, 0 until it has been calibrated.
+static FREQUENCY: AtomicU64 = AtomicU64::new(0);
+static REFERENCE: Once<Reference> = Once::new();
+
+/// The clock the TSC was calibrated against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    Pit,
+    Hpet,
+}
+
+/// Returns the current value of the time stamp counter.
+pub fn read() -> u64 {
+    // SAFETY: Every x86_64 CPU has a TSC.
+    unsafe { _rdtsc() }
+}
+
+/// Returns whether the TSC runs at a constant rate in all power states.
+///
+/// Without an invariant TSC, measurements can be off if the CPU changes
+/// its frequency.
+pub fn is_invariant() -> bool {
+    if __cpuid(CPUID_EXTENDED_MAX_LEAF).eax < CPUID_ADVANCED_POWER_MANAGEMENT {
+        return false;
+    }
+    __cpuid(CPUID_ADVANCED_POWER_MANAGEMENT).edx & CPUID_APM_EDX_INVARIANT_TSC != 0
+}
+
+/// Measure the frequency of the TSC, using the HPET if there is one and the
+/// PIT otherwise.
+///
+/// Must be called with interrupts disabled, since an interrupt during the
+/// measurement would skew the result.
+pub fn calibrate() {
+    let (frequency, reference) = match calibrate_hpet() {
+        Some(frequency) => (frequency, Reference::Hpet),
+        None => (calibrate_pit(), Reference::Pit),
+    };
+    FREQUENCY.store(frequency, Ordering::SeqCst);
+    REFERENCE.call_once(|| reference);
+}
+
+/// Returns the calibrated TSC frequency in Hz, if [`calibrate`] was called.
+pub fn frequency() -> Option<u64> {
+    match FREQUENCY.load(Ordering::SeqCst) {
+        0 => None,
+        frequency => Some(frequency),
+    }
+}
+
+/// Returns the clock the TSC was calibrated against.
+pub fn reference() -> Option<Reference> {
+    REFERENCE.get().copied()
+}
+
+/// Measure the TSC against PIT channel 2, which counts down without
+/// raising interrupts.
+fn calibrate_pit() -> u64 {
+    const CHANNEL_2_DATA_PORT: u16 = 0x42;
+    const COMMAND_PORT: u16 = 0x43;
+    /// Channel 2, low byte then high byte, mode 0 (interrupt on terminal
+    /// count), binary.
+    const COMMAND_CHANNEL_2_ONE_SHOT: u8 = 0xb0;
+    const SPEAKER_PORT: u16 = 0x61;
+    const SPEAKER_GATE: u8 = 1 << 0;
+    const SPEAKER_ENABLE: u8 = 1 << 1;
+    const CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+    let count =
+        u64::from(pit::BASE_FREQUENCY) * CALIBRATION_TIME.as_nanos() as u64 / NANOS_PER_SEC as u64;
+
+    let mut speaker = Port::<u8>::new(SPEAKER_PORT);
+    let mut command = Port::<u8>::new(COMMAND_PORT);
+    let mut data = Port::<u8>::new(CHANNEL_2_DATA_PORT);
+    // SAFETY: Channel 2 is only used here, with the speaker disconnected.
+    let (start, end) = unsafe {
+        let control = speaker.read() & !(SPEAKER_GATE | SPEAKER_ENABLE);
+        speaker.write(control);
+
+        command.write(COMMAND_CHANNEL_2_ONE_SHOT);
+        data.write(count as u8);
+        data.write((count >> 8) as u8);
+
+        // raising the gate starts the countdown
+        speaker.write(control | SPEAKER_GATE);
+        let start = read();
+        while speaker.read() & CHANNEL_2_OUTPUT == 0 {
+            core::hint::spin_loop();
+        }
+        let end = read();
+
+        speaker.write(control);
+        (start, end)
+    };
+
+    (end - start) * u64::from(pit::BASE_FREQUENCY) / count
+}
+
+/// Measure the TSC against the main counter of the HPET.
+fn calibrate_hpet() -> Option<u64> {
+    let hpet = super::hpet::get()?;
+    let count = hpet.duration_to_ticks(CALIBRATION_TIME);
+
+    let counter_start = hpet.counter();
+    let start = read();
+    let mut counter_end = counter_start;
+    while counter_end.wrapping_sub(counter_start) < count {
+        counter_end = hpet.counter();
+    }
+    let end = read();
+
+    let elapsed = hpet.ticks_to_duration(counter_end.wrapping_sub(counter_start));
+    Some((u128::from(end - start) * NANOS_PER_SEC / elapsed.as_nanos()) as u64)
+}
+
+/// A point in time measured with the TSC, with nanosecond resolution.
+///
+/// Durations are only meaningful once the TSC has been calibrated, before
+/// that they are always zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    pub fn now() -> Self {
+        Self(read())
+    }
+
+    /// Returns the time elapsed since `earlier`, or zero if `earlier` is
+    /// later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        cycles_to_duration(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Returns the time elapsed since this instant.
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant(self.0 + duration_to_cycles(duration))
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        Instant(self.0 - duration_to_cycles(duration))
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+fn cycles_to_duration(cycles: u64) -> Duration {
+    match frequency() {
+        Some(frequency) => {
+            let nanos = u128::from(cycles) * NANOS_PER_SEC / u128::from(frequency);
+            Duration::from_nanos(nanos as u64)
+        }
+        None => Duration::ZERO,
+    }
+}
+
+fn duration_to_cycles(duration: Duration) -> u64 {
+    let frequency = u128::from(frequency().unwrap_or(0));
+    (duration.as_nanos() * frequency / NANOS_PER_SEC) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_calibrated() {
+        let frequency = frequency().expect("TSC not calibrated");
+        // anything below 100 MHz is surely wrong
+        assert!(frequency > 100_000_000, "TSC frequency {} Hz", frequency);
+    }
+
+    #[test_case]
+    fn test_instant_is_monotonic() {
+        let first = Instant::now();
+        let second = Instant::now();
+        assert!(second >= first);
+        assert_eq!(first.duration_since(second), Duration::ZERO);
+    }
+
+    #[test_case]
+    fn test_instant_matches_ticks() {
+        let start = Instant::now();
+        super::super::timer::sleep(Duration::from_millis(20));
+        let elapsed = start.elapsed();
+        // the sleep lasts at least 20 ms, the calibration is accurate to
+        // well within 10%
+        assert!(elapsed >= Duration::from_millis(18), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(100), "{:?}", elapsed);
+    }
+
+    #[test_case]
+    fn test_instant_arithmetic() {
+        let start = Instant::now();
+        let later = start + Duration::from_millis(5);
+        let difference = later - start;
+        assert!(difference.abs_diff(Duration::from_millis(5)) < Duration::from_micros(1));
+        assert_eq!(later - Duration::from_millis(5), start);
+    }
+}
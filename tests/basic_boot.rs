@@ -6,6 +6,8 @@
 
 use core::panic::PanicInfo;
 
+use bootloader::{entry_point, BootInfo};
+
 use os::vga_println;
 
 #[panic_handler]
@@ -13,9 +15,10 @@ fn panic(info: &PanicInfo) -> ! {
     os::testing::test_panic_handler(info)
 }
 
-#[no_mangle]
-pub extern "C" fn _start() -> ! {
-    os::init();
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    os::init(boot_info);
 
     test_main();
 
@@ -0,0 +1,124 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(os::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::alloc::{alloc, dealloc};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::panic::PanicInfo;
+
+use bootloader::{entry_point, BootInfo};
+
+use os::allocator::{heap_size, HEAP_MAX_SIZE, HEAP_SIZE};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    os::init(boot_info);
+
+    test_main();
+
+    os::hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    os::testing::test_panic_handler(info)
+}
+
+#[test_case]
+fn simple_allocation() {
+    let heap_value_1 = Box::new(41);
+    let heap_value_2 = Box::new(13);
+    assert_eq!(*heap_value_1, 41);
+    assert_eq!(*heap_value_2, 13);
+}
+
+#[test_case]
+fn large_vec() {
+    let n = 1000;
+    let mut vec = Vec::new();
+    for i in 0..n {
+        vec.push(i);
+    }
+    assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
+}
+
+#[test_case]
+fn many_boxes() {
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+}
+
+#[test_case]
+fn many_boxes_long_lived() {
+    let long_lived = Box::new(1);
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+    assert_eq!(*long_lived, 1);
+}
+
+#[test_case]
+fn many_vecs_of_different_sizes() {
+    for round in 0..HEAP_SIZE / 1024 {
+        let mut vecs = Vec::new();
+        for size in [1, 7, 64, 300, 2048, 5000] {
+            let mut v = Vec::with_capacity(size);
+            v.resize(size, round as u8);
+            vecs.push(v);
+        }
+        for v in &vecs {
+            assert!(v.iter().all(|&b| b == round as u8));
+        }
+    }
+}
+
+#[test_case]
+fn large_allocations_are_reused() {
+    let size_before = heap_size();
+    // Every allocation takes half of the initial heap, so the heap would
+    // have to grow if freed memory wasn't handed out again.
+    for _ in 0..10 {
+        let v: Vec<u8> = Vec::with_capacity(HEAP_SIZE / 2);
+        assert_eq!(v.capacity(), HEAP_SIZE / 2);
+    }
+    assert_eq!(heap_size(), size_before);
+}
+
+#[test_case]
+fn freed_memory_is_reused() {
+    let first = Box::new(42u64);
+    let first_addr = &*first as *const u64 as usize;
+    drop(first);
+
+    let second = Box::new(13u64);
+    let second_addr = &*second as *const u64 as usize;
+    assert_eq!(first_addr, second_addr);
+}
+
+#[test_case]
+fn heap_grows_on_demand() {
+    let v: Vec<u8> = Vec::with_capacity(HEAP_SIZE * 4);
+    assert_eq!(v.capacity(), HEAP_SIZE * 4);
+    assert!(heap_size() > HEAP_SIZE * 4);
+}
+
+#[test_case]
+fn allocation_over_limit_fails() {
+    let layout = Layout::from_size_align(HEAP_MAX_SIZE + 1, 8).unwrap();
+    let ptr = unsafe { alloc(layout) };
+    if !ptr.is_null() {
+        unsafe { dealloc(ptr, layout) };
+    }
+    assert!(ptr.is_null());
+    assert!(heap_size() <= HEAP_MAX_SIZE);
+}
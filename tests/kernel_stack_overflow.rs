@@ -4,7 +4,9 @@
 
 use core::panic::PanicInfo;
 use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+use bootloader::{entry_point, BootInfo};
 use lazy_static::lazy_static;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 
@@ -16,8 +18,10 @@ fn panic(info: &PanicInfo) -> ! {
     os::testing::test_panic_handler(info)
 }
 
-#[no_mangle]
-pub extern "C" fn _start() -> ! {
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    os::init_memory(boot_info);
     os::gdt::init();
     test_init_idt();
 
@@ -56,11 +60,30 @@ lazy_static! {
     };
 }
 
+/// Number of double faults taken so far.
+static DOUBLE_FAULTS: AtomicUsize = AtomicUsize::new(0);
+
 extern "x86-interrupt" fn test_double_fault_handler(_: InterruptStackFrame, _: u64) -> ! {
-    serial_println!("[ok]");
-    exit_qemu(QemuExitCode::Success);
+    match DOUBLE_FAULTS.fetch_add(1, Ordering::SeqCst) {
+        0 => {
+            serial_println!("[ok]");
+            serial_print!("stack_overflow::ist_stack_overflow...\t");
+
+            // Overflow the IST stack itself. Hitting its guard page causes
+            // another double fault, which starts again at the top of the
+            // IST stack. Without the guard page the recursion would
+            // silently overwrite the memory below the stack.
+            stack_overflow();
+
+            panic!("Execution continued after IST stack overflow");
+        }
+        _ => {
+            serial_println!("[ok]");
+            exit_qemu(QemuExitCode::Success);
 
-    os::hlt_loop()
+            os::hlt_loop()
+        }
+    }
 }
 
 pub fn test_init_idt() {
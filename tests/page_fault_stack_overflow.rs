@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+#![feature(abi_x86_interrupt)]
+
+use core::panic::PanicInfo;
+use core::ptr;
+
+use bootloader::{entry_point, BootInfo};
+use lazy_static::lazy_static;
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+
+use os::testing::{exit_qemu, QemuExitCode};
+use os::{serial_print, serial_println};
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    os::testing::test_panic_handler(info)
+}
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    os::init_memory(boot_info);
+    os::gdt::init();
+    test_init_idt();
+
+    serial_print!("page_fault_stack_overflow::stack_overflow...\t");
+
+    // trigger a stack overflow
+    stack_overflow();
+
+    panic!("Execution continued after stack overflow");
+}
+
+#[allow(unconditional_recursion)]
+fn stack_overflow() {
+    stack_overflow();
+
+    // Perform a volatile read to prevent
+    // `tail call elimination` optimisation.
+    let x = 0;
+
+    // SAFETY: x is alive on the stack
+    unsafe {
+        ptr::addr_of!(x).read_volatile();
+    }
+}
+
+lazy_static! {
+    static ref TEST_IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+        unsafe {
+            idt.page_fault
+                .set_handler_fn(test_page_fault_handler)
+                .set_stack_index(os::gdt::PAGE_FAULT_IST_INDEX);
+            idt.double_fault
+                .set_handler_fn(test_double_fault_handler)
+                .set_stack_index(os::gdt::DOUBLE_FAULT_IST_INDEX);
+        }
+
+        idt
+    };
+}
+
+extern "x86-interrupt" fn test_page_fault_handler(_: InterruptStackFrame, _: PageFaultErrorCode) {
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+
+    os::hlt_loop()
+}
+
+extern "x86-interrupt" fn test_double_fault_handler(_: InterruptStackFrame, _: u64) -> ! {
+    serial_println!("[page fault escalated to a double fault]");
+    exit_qemu(QemuExitCode::Failed);
+
+    os::hlt_loop()
+}
+
+pub fn test_init_idt() {
+    TEST_IDT.load();
+}
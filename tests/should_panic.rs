@@ -3,6 +3,8 @@
 
 use core::panic::PanicInfo;
 
+use bootloader::{entry_point, BootInfo};
+
 use os::testing::{exit_qemu, QemuExitCode};
 use os::{serial_print, serial_println};
 
@@ -14,9 +16,10 @@ fn handler(_: &PanicInfo) -> ! {
     os::hlt_loop()
 }
 
-#[no_mangle]
-pub extern "C" fn _start() -> ! {
-    os::init();
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    os::init(boot_info);
 
     should_fail();
     serial_println!("[test did not panic]");
@@ -0,0 +1,259 @@
+//! Embeds the function symbols of the kernel ELF into its `.ksymtab` section.
+//!
+//! The kernel reserves the (zeroed) section, see `src/symbols.rs`. This tool
+//! runs after linking and fills it with a table of the form
+//!
+//! ```text
+//! magic:   b"KSYM"
+//! count:   u32
+//! entries: [{ address: u64, size: u32, name: u32 }; count], sorted by address
+//! names:   NUL terminated demangled names, `name` is an offset into them
+//! ```
+//!
+//! with all integers in little endian.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::process;
+
+const SECTION_NAME: &str = ".ksymtab";
+const MAGIC: &[u8; 4] = b"KSYM";
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const STT_FUNC: u8 = 2;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(Error(format!($($arg)*)))
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Section {
+    name: u32,
+    kind: u32,
+    offset: usize,
+    size: usize,
+    link: u32,
+}
+
+struct Elf<'a> {
+    data: &'a [u8],
+    sections: Vec<Section>,
+    names: Section,
+}
+
+impl<'a> Elf<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 64 || &data[..4] != b"\x7fELF" {
+            bail!("not an ELF file");
+        }
+        // 64 bit, little endian
+        if data[4] != 2 || data[5] != 1 {
+            bail!("not a 64 bit little endian ELF file");
+        }
+
+        let section_headers = read_u64(data, 0x28)? as usize;
+        let header_size = read_u16(data, 0x3a)? as usize;
+        let count = read_u16(data, 0x3c)? as usize;
+        let names_index = read_u16(data, 0x3e)? as usize;
+        if header_size != SECTION_HEADER_SIZE {
+            bail!("unexpected section header size {}", header_size);
+        }
+
+        let sections = (0..count)
+            .map(|i| {
+                let header = section_headers + i * SECTION_HEADER_SIZE;
+                Ok(Section {
+                    name: read_u32(data, header)?,
+                    kind: read_u32(data, header + 4)?,
+                    offset: read_u64(data, header + 24)? as usize,
+                    size: read_u64(data, header + 32)? as usize,
+                    link: read_u32(data, header + 40)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let names = *sections
+            .get(names_index)
+            .ok_or_else(|| Error("missing section name table".into()))?;
+
+        Ok(Self {
+            data,
+            sections,
+            names,
+        })
+    }
+
+    fn section_by_name(&self, name: &str) -> Result<Option<Section>, Error> {
+        for section in &self.sections {
+            if read_str(self.data, self.names.offset + section.name as usize)? == name {
+                return Ok(Some(*section));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns all defined functions as `(address, size, mangled name)`.
+    fn functions(&self) -> Result<Vec<(u64, u64, &'a str)>, Error> {
+        let Some(symtab) = self.sections.iter().find(|s| s.kind == SHT_SYMTAB) else {
+            return Ok(Vec::new());
+        };
+        let strtab = self
+            .sections
+            .get(symtab.link as usize)
+            .ok_or_else(|| Error("missing symbol string table".into()))?;
+
+        let mut functions = Vec::new();
+        for i in 0..symtab.size / SYMBOL_SIZE {
+            let symbol = symtab.offset + i * SYMBOL_SIZE;
+            let info = self.data[symbol + 4];
+            let address = read_u64(self.data, symbol + 8)?;
+            let size = read_u64(self.data, symbol + 16)?;
+            if info & 0xf != STT_FUNC || address == 0 || size == 0 {
+                continue;
+            }
+
+            let name = read_str(self.data, strtab.offset + read_u32(self.data, symbol)? as usize)?;
+            functions.push((address, size, name));
+        }
+
+        Ok(functions)
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], Error> {
+    data.get(offset..offset + N)
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or_else(|| Error(format!("offset {:#x} is out of bounds", offset)))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, Error> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+fn read_str(data: &[u8], offset: usize) -> Result<&str, Error> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| Error(format!("offset {:#x} is out of bounds", offset)))?;
+    let len = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| Error("unterminated string".into()))?;
+    std::str::from_utf8(&bytes[..len]).map_err(|_| Error("invalid UTF-8 in a name".into()))
+}
+
+/// Build the table from `(address, size, mangled name)` triples.
+fn build_table(mut functions: Vec<(u64, u64, &str)>) -> Vec<u8> {
+    functions.sort_by_key(|&(address, _, _)| address);
+    functions.dedup_by_key(|&mut (address, _, _)| address);
+
+    let mut entries = Vec::new();
+    let mut names = Vec::new();
+    for (address, size, name) in &functions {
+        entries.extend_from_slice(&address.to_le_bytes());
+        entries.extend_from_slice(&(*size as u32).to_le_bytes());
+        entries.extend_from_slice(&(names.len() as u32).to_le_bytes());
+        // the alternate format omits the hash suffix
+        names.extend_from_slice(format!("{:#}", rustc_demangle::demangle(name)).as_bytes());
+        names.push(0);
+    }
+
+    let mut table = Vec::new();
+    table.extend_from_slice(MAGIC);
+    table.extend_from_slice(&(functions.len() as u32).to_le_bytes());
+    table.extend_from_slice(&entries);
+    table.extend_from_slice(&names);
+    table
+}
+
+fn run(path: &str) -> Result<usize, Error> {
+    let mut data = fs::read(path).map_err(|err| Error(format!("failed to read {}: {}", path, err)))?;
+
+    let (section, table) = {
+        let elf = Elf::parse(&data)?;
+        let section = match elf.section_by_name(SECTION_NAME)? {
+            Some(section) if section.kind != SHT_NOBITS => section,
+            Some(_) => bail!("{} has no contents in the file", SECTION_NAME),
+            None => bail!("the kernel has no {} section", SECTION_NAME),
+        };
+        (section, build_table(elf.functions()?))
+    };
+
+    if table.len() > section.size {
+        bail!(
+            "the symbol table needs {} bytes, but {} only has {}; increase SYMTAB_SIZE in src/symbols.rs",
+            table.len(),
+            SECTION_NAME,
+            section.size
+        );
+    }
+
+    let contents = &mut data[section.offset..section.offset + section.size];
+    contents.fill(0);
+    contents[..table.len()].copy_from_slice(&table);
+    fs::write(path, &data).map_err(|err| Error(format!("failed to write {}: {}", path, err)))?;
+
+    Ok(read_u32(&table, 4)? as usize)
+}
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: ksymtab <kernel ELF>");
+        process::exit(2);
+    };
+
+    match run(&path) {
+        Ok(0) => eprintln!("ksymtab: warning: no function symbols found, is the kernel stripped?"),
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("ksymtab: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted_and_demangled() {
+        let table = build_table(vec![
+            (0x2000, 0x10, "_ZN2os4main17h0123456789abcdefE"),
+            (0x1000, 0x20, "_start"),
+            (0x1000, 0x20, "_start_alias"),
+        ]);
+
+        assert_eq!(&table[..4], MAGIC);
+        assert_eq!(read_u32(&table, 4).unwrap(), 2);
+        assert_eq!(read_u64(&table, 8).unwrap(), 0x1000);
+        assert_eq!(read_u32(&table, 16).unwrap(), 0x20);
+        assert_eq!(read_u64(&table, 24).unwrap(), 0x2000);
+
+        let names = 8 + 2 * 16;
+        let second = read_u32(&table, 36).unwrap() as usize;
+        assert_eq!(read_str(&table, names).unwrap(), "_start");
+        assert_eq!(read_str(&table, names + second).unwrap(), "os::main");
+    }
+}
@@ -0,0 +1,11 @@
+#!/bin/sh
+# Cargo runner: embeds the symbol table into the kernel and boots it.
+set -e
+
+root="$(cd "$(dirname "$0")/.." && pwd)"
+
+# Build the tool from outside of the repository, so that the kernel's
+# `.cargo/config.toml` (custom target, build-std) doesn't apply to it.
+(cd / && cargo run --quiet --release --manifest-path "$root/tools/ksymtab/Cargo.toml" -- "$1")
+
+exec bootimage runner "$@"